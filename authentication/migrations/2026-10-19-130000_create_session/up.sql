@@ -0,0 +1,8 @@
+
+CREATE TABLE session (
+    id TEXT PRIMARY KEY,
+    state TEXT NOT NULL,
+    expires_at TIMESTAMP NOT NULL
+);
+
+CREATE INDEX session_expires_at_idx ON session (expires_at);
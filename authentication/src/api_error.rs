@@ -2,6 +2,7 @@ use actix_web::http::StatusCode;
 use actix_web::{HttpResponse, ResponseError};
 use actix_web::error::Error as ActixError;
 use diesel::result::Error as DieselError;
+use redis::RedisError;
 use serde::Deserialize;
 use serde_json::json;
 use std::fmt;
@@ -34,6 +35,12 @@ impl From<DieselError> for ApiError {
     }
 }
 
+impl From<RedisError> for ApiError {
+    fn from(error: RedisError) -> ApiError {
+        ApiError::new(500, format!("Redis error: {}", error))
+    }
+}
+
 impl From<ActixError> for ApiError {
     fn from(error: ActixError) -> ApiError {
         ApiError::new(500, error.to_string())
@@ -1,3 +1,4 @@
 mod routes;
+mod tests;
 
 pub use routes::init_routes;
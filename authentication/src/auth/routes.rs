@@ -15,24 +15,29 @@ async fn register(user: web::Json<UserMessage>) -> Result<HttpResponse, ApiError
 async fn sign_in(credentials: web::Json<UserMessage>, session: Session) -> Result<HttpResponse, ApiError> {
     let credentials = credentials.into_inner();
 
-    let user = User::find_by_email(credentials.email)
-        .map_err(|e| {
-            match e.status_code {
-                404 => ApiError::new(401, "Credentials not valid!".to_string()),
-                _ => e,
+    let user = match User::find_by_email(credentials.email) {
+        Ok(user) => Some(user),
+        Err(e) if e.status_code == 404 => None,
+        Err(e) => return Err(e),
+    };
+
+    let is_valid = match &user {
+        Some(user) => user.verify_password(credentials.password.as_bytes())?,
+        None => User::verify_dummy_password(credentials.password.as_bytes())?,
+    };
+
+    match user {
+        Some(mut user) if is_valid => {
+            if let Err(e) = user.rehash_password_if_outdated(&credentials.password) {
+                error!("Failed to rehash password: {}", e);
             }
-        })?;
-    
-    let is_valid = user.verify_password(credentials.password.as_bytes())?;
 
-    if is_valid == true {
-        session.set("user_id", user.id)?;
-        session.renew();
+            session.set("user_id", user.id)?;
+            session.renew();
 
-        Ok(HttpResponse::Ok().json(user))
-    }
-    else {
-        Err(ApiError::new(401, "Credentials not valid!".to_string()))
+            Ok(HttpResponse::Ok().json(user))
+        },
+        _ => Err(ApiError::new(401, "Credentials not valid!".to_string())),
     }
 }
 
@@ -0,0 +1,142 @@
+#[cfg(test)]
+mod tests {
+    use crate::auth::init_routes;
+    use crate::session::{MemoryStore, StoreSession};
+    use crate::db;
+    use crate::schema::user;
+    use crate::user::{User, UserMessage, PASSWORD_CONFIG};
+    use argon2::Config;
+    use diesel::prelude::*;
+    use actix_web::{test::{self, TestRequest}, App};
+    use serde_json::json;
+    use std::sync::Arc;
+    use uuid::Uuid;
+
+    #[actix_rt::test]
+    async fn test_sign_in() {
+        crate::test::init();
+
+        let email = format!("{}@cloudmaker.dev", Uuid::new_v4());
+        let user = User::create(UserMessage { email: email.clone(), password: "test".to_string() })
+            .expect("Failed to create user");
+
+        let mut app = test::init_service(
+            App::new()
+                .wrap(StoreSession::new(Arc::new(MemoryStore::new()), &[0; 32]))
+                .configure(init_routes)
+        ).await;
+
+        let req = TestRequest::get().uri("/who-am-i").to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(401, resp.status().as_u16(), "Should not be signed in before signing in");
+
+        let request_body = json!({
+            "email": email,
+            "password": "test",
+        });
+
+        let req = TestRequest::post().uri("/sign-in").set_json(&request_body).to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert!(resp.status().is_success(), "Failed to sign in");
+
+        let cookie = resp.response()
+            .cookies()
+            .find(|cookie| cookie.name() == "actix-session")
+            .expect("Sign in should set a session cookie")
+            .into_owned();
+
+        let req = TestRequest::get().uri("/who-am-i").cookie(cookie.clone()).to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert!(resp.status().is_success(), "Failed to find signed in user");
+
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(json!(user.id), body["id"], "Found wrong user");
+
+        let req = TestRequest::post().uri("/sign-out").cookie(cookie.clone()).to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert!(resp.status().is_success(), "Failed to sign out");
+
+        let req = TestRequest::get().uri("/who-am-i").cookie(cookie).to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(401, resp.status().as_u16(), "Session should not be valid after signing out");
+
+        User::delete(user.id).expect("Failed to delete user");
+    }
+
+    #[actix_rt::test]
+    async fn test_sign_in_does_not_reveal_unknown_email() {
+        crate::test::init();
+
+        let email = format!("{}@cloudmaker.dev", Uuid::new_v4());
+        let user = User::create(UserMessage { email: email.clone(), password: "test".to_string() })
+            .expect("Failed to create user");
+
+        let mut app = test::init_service(
+            App::new()
+                .wrap(StoreSession::new(Arc::new(MemoryStore::new()), &[0; 32]))
+                .configure(init_routes)
+        ).await;
+
+        let request_body = json!({
+            "email": email,
+            "password": "wrong",
+        });
+
+        let req = TestRequest::post().uri("/sign-in").set_json(&request_body).to_request();
+        let resp = test::call_service(&mut app, req).await;
+        let wrong_password_status = resp.status();
+        let wrong_password_body = test::read_body(resp).await;
+
+        let request_body = json!({
+            "email": format!("{}@cloudmaker.dev", Uuid::new_v4()),
+            "password": "wrong",
+        });
+
+        let req = TestRequest::post().uri("/sign-in").set_json(&request_body).to_request();
+        let resp = test::call_service(&mut app, req).await;
+        let unknown_email_status = resp.status();
+        let unknown_email_body = test::read_body(resp).await;
+
+        User::delete(user.id).expect("Failed to delete user");
+
+        assert_eq!(401, wrong_password_status.as_u16(), "Wrong password should be rejected");
+        assert_eq!(wrong_password_status, unknown_email_status, "Unknown email should get the same status as a wrong password");
+        assert_eq!(wrong_password_body, unknown_email_body, "Unknown email should get the same body as a wrong password");
+    }
+
+    #[actix_rt::test]
+    async fn test_sign_in_rehashes_outdated_password() {
+        crate::test::init();
+
+        let email = format!("{}@cloudmaker.dev", Uuid::new_v4());
+        let user = User::create(UserMessage { email: email.clone(), password: "test".to_string() })
+            .expect("Failed to create user");
+
+        let current = PASSWORD_CONFIG.argon2();
+        let outdated = Config { time_cost: current.time_cost + 1, ..PASSWORD_CONFIG.argon2() };
+        let outdated = argon2::hash_encoded(b"test", &[0; 16], &outdated).unwrap();
+
+        let conn = db::connection().unwrap();
+        diesel::update(user::table.filter(user::id.eq(user.id)))
+            .set(user::password.eq(&outdated))
+            .execute(&conn)
+            .unwrap();
+
+        let mut app = test::init_service(
+            App::new()
+                .wrap(StoreSession::new(Arc::new(MemoryStore::new()), &[0; 32]))
+                .configure(init_routes)
+        ).await;
+
+        let request_body = json!({ "email": email, "password": "test" });
+        let req = TestRequest::post().uri("/sign-in").set_json(&request_body).to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert!(resp.status().is_success(), "Failed to sign in with an outdated hash");
+
+        let rehashed = User::find(user.id).unwrap();
+        assert!(PASSWORD_CONFIG.is_current(&rehashed.password), "Outdated hash should be replaced on sign in");
+        assert!(rehashed.verify_password(b"test").unwrap(), "The new hash should still match the password");
+
+        User::delete(user.id).expect("Failed to delete user");
+    }
+}
@@ -5,7 +5,6 @@ extern crate diesel;
 #[macro_use]
 extern crate diesel_migrations;
 
-use actix_redis::RedisSession;
 use actix_web::{App, HttpServer};
 use dotenv::dotenv;
 use listenfd::ListenFd;
@@ -14,24 +13,28 @@ use std::env;
 mod api_error;
 mod db;
 mod schema;
+mod session;
 mod auth;
 mod user;
 
+#[cfg(test)]
+mod test;
+
 #[actix_rt::main]
 async fn main() -> std::io::Result<()> {
     dotenv().ok();
     env_logger::init();
 
     db::init();
+    user::init();
 
     let mut listenfd = ListenFd::from_env();
 
-    let redis_port = env::var("REDIS_PORT").expect("Redis port not set");
-    let redis_host = env::var("REDIS_HOST").expect("Redis host not set");
+    let session_store = session::from_env();
 
     let mut server = HttpServer::new(move|| 
         App::new()
-            .wrap(RedisSession::new(format!("{}:{}", redis_host, redis_port), &[0; 32]))
+            .wrap(session::StoreSession::new(session_store.clone(), &[0; 32]))
             .configure(auth::init_routes)
     );
 
@@ -1,3 +1,11 @@
+table! {
+    session (id) {
+        id -> Text,
+        state -> Text,
+        expires_at -> Timestamp,
+    }
+}
+
 table! {
     user (id) {
         id -> Uuid,
@@ -7,3 +15,8 @@ table! {
         updated_at -> Nullable<Timestamp>,
     }
 }
+
+allow_tables_to_appear_in_same_query!(
+    session,
+    user,
+);
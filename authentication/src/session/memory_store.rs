@@ -0,0 +1,48 @@
+use crate::api_error::ApiError;
+use crate::session::{SessionState, SessionStore};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// Keeps sessions in process, so they are lost on restart and not shared between instances.
+// Mostly useful for development and tests.
+pub struct MemoryStore {
+    sessions: Mutex<HashMap<String, (SessionState, Instant)>>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        MemoryStore { sessions: Mutex::new(HashMap::new()) }
+    }
+}
+
+impl SessionStore for MemoryStore {
+    fn load(&self, key: &str) -> Result<Option<SessionState>, ApiError> {
+        let mut sessions = self.sessions.lock()
+            .map_err(|_| ApiError::new(500, "Session store lock poisoned".to_string()))?;
+
+        let now = Instant::now();
+        sessions.retain(|_, (_, expires_at)| *expires_at > now);
+
+        Ok(sessions.get(key).map(|(state, _)| state.clone()))
+    }
+
+    fn save(&self, key: &str, state: &SessionState, ttl: i64) -> Result<(), ApiError> {
+        let mut sessions = self.sessions.lock()
+            .map_err(|_| ApiError::new(500, "Session store lock poisoned".to_string()))?;
+
+        let expires_at = Instant::now() + Duration::from_secs(ttl as u64);
+        sessions.insert(key.to_string(), (state.clone(), expires_at));
+
+        Ok(())
+    }
+
+    fn delete(&self, key: &str) -> Result<(), ApiError> {
+        let mut sessions = self.sessions.lock()
+            .map_err(|_| ApiError::new(500, "Session store lock poisoned".to_string()))?;
+
+        sessions.remove(key);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,162 @@
+use crate::session::{SessionState, SessionStore};
+use actix_service::{Service, Transform};
+use actix_session::{Session, SessionStatus};
+use actix_web::cookie::{Cookie, CookieJar, Key};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::{Error, HttpMessage};
+use futures::future::{ok, Ready};
+use rand::Rng;
+use rand::distributions::Alphanumeric;
+use std::cell::RefCell;
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+const COOKIE_NAME: &str = "actix-session";
+const TTL: i64 = 7 * 24 * 60 * 60;
+
+// Session middleware that keeps the session state in any SessionStore,
+// while the client only gets a signed cookie with the session key.
+pub struct StoreSession(Rc<Inner>);
+
+struct Inner {
+    store: Arc<dyn SessionStore>,
+    key: Key,
+}
+
+impl StoreSession {
+    pub fn new(store: Arc<dyn SessionStore>, key: &[u8]) -> Self {
+        StoreSession(Rc::new(Inner { store, key: Key::from_master(key) }))
+    }
+}
+
+impl<S, B> Transform<S> for StoreSession
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = StoreSessionMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ok(StoreSessionMiddleware {
+            service: Rc::new(RefCell::new(service)),
+            inner: self.0.clone(),
+        })
+    }
+}
+
+pub struct StoreSessionMiddleware<S> {
+    service: Rc<RefCell<S>>,
+    inner: Rc<Inner>,
+}
+
+impl<S, B> Service for StoreSessionMiddleware<S>
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    fn poll_ready(&mut self, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        self.service.borrow_mut().poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: ServiceRequest) -> Self::Future {
+        let service = self.service.clone();
+        let inner = self.inner.clone();
+
+        Box::pin(async move {
+            let session_key = inner.session_key(&req);
+
+            if let Some(session_key) = &session_key {
+                if let Some(state) = inner.store.load(session_key)? {
+                    Session::set_session(state.into_iter(), &mut req);
+                }
+            }
+
+            let fut = service.borrow_mut().call(req);
+            let mut res = fut.await?;
+
+            match Session::get_changes(&mut res) {
+                (SessionStatus::Changed, Some(state)) => {
+                    inner.save(res, state.collect(), session_key)
+                },
+                (SessionStatus::Renewed, Some(state)) => {
+                    if let Some(session_key) = session_key {
+                        inner.store.delete(&session_key)?;
+                    }
+                    inner.save(res, state.collect(), None)
+                },
+                (SessionStatus::Purged, _) => {
+                    if let Some(session_key) = session_key {
+                        inner.store.delete(&session_key)?;
+                    }
+                    inner.remove_cookie(res)
+                },
+                _ => Ok(res),
+            }
+        })
+    }
+}
+
+impl Inner {
+    fn session_key(&self, req: &ServiceRequest) -> Option<String> {
+        let cookie = req.cookie(COOKIE_NAME)?;
+
+        let mut jar = CookieJar::new();
+        jar.add_original(cookie);
+
+        jar.signed(&self.key)
+            .get(COOKIE_NAME)
+            .map(|cookie| cookie.value().to_string())
+    }
+
+    fn save<B>(&self, mut res: ServiceResponse<B>, state: SessionState, session_key: Option<String>) -> Result<ServiceResponse<B>, Error> {
+        if let Some(session_key) = session_key {
+            self.store.save(&session_key, &state, TTL)?;
+            return Ok(res);
+        }
+
+        let session_key: String = rand::thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(32)
+            .collect();
+
+        self.store.save(&session_key, &state, TTL)?;
+
+        let mut cookie = Cookie::new(COOKIE_NAME, session_key);
+        cookie.set_path("/");
+        cookie.set_http_only(true);
+
+        let mut jar = CookieJar::new();
+        jar.signed(&self.key).add(cookie);
+
+        for cookie in jar.delta() {
+            res.response_mut().add_cookie(cookie)?;
+        }
+
+        Ok(res)
+    }
+
+    fn remove_cookie<B>(&self, mut res: ServiceResponse<B>) -> Result<ServiceResponse<B>, Error> {
+        let mut cookie = Cookie::named(COOKIE_NAME);
+        cookie.set_path("/");
+        cookie.make_removal();
+
+        res.response_mut().add_cookie(&cookie)?;
+
+        Ok(res)
+    }
+}
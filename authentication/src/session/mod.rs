@@ -0,0 +1,12 @@
+mod memory_store;
+mod middleware;
+mod postgres_store;
+mod redis_store;
+mod store;
+mod tests;
+
+pub use memory_store::MemoryStore;
+pub use middleware::StoreSession;
+pub use postgres_store::PostgresStore;
+pub use redis_store::RedisStore;
+pub use store::{from_env, SessionState, SessionStore};
@@ -0,0 +1,92 @@
+use crate::api_error::ApiError;
+use crate::db;
+use crate::schema::session;
+use crate::session::{SessionState, SessionStore};
+use actix_web::web;
+use chrono::{Duration, Utc};
+use diesel::prelude::*;
+use std::env;
+
+pub struct PostgresStore;
+
+impl PostgresStore {
+    // Sessions that expire are only skipped when loading, so the rows are deleted every
+    // SESSION_CLEANUP_INTERVAL seconds. Has to be called from within the actix runtime.
+    pub fn start_cleanup() {
+        let interval = env::var("SESSION_CLEANUP_INTERVAL")
+            .map(|interval| interval.parse().expect("Session cleanup interval must be a number of seconds"))
+            .unwrap_or(3600);
+
+        actix_rt::spawn(async move {
+            let mut interval = actix_rt::time::interval(std::time::Duration::from_secs(interval));
+            loop {
+                interval.tick().await;
+                match web::block(PostgresStore::delete_expired).await {
+                    Ok(0) => (),
+                    Ok(deleted) => info!("Deleted {} expired sessions", deleted),
+                    Err(e) => error!("Failed to delete expired sessions: {}", e),
+                }
+            }
+        });
+    }
+
+    pub fn delete_expired() -> Result<usize, ApiError> {
+        let conn = db::connection()?;
+
+        let res = diesel::delete(
+                session::table
+                    .filter(session::expires_at.le(Utc::now().naive_utc()))
+            )
+            .execute(&conn)?;
+
+        Ok(res)
+    }
+}
+
+impl SessionStore for PostgresStore {
+    fn load(&self, key: &str) -> Result<Option<SessionState>, ApiError> {
+        let conn = db::connection()?;
+
+        let state = session::table
+            .select(session::state)
+            .filter(session::id.eq(key))
+            .filter(session::expires_at.gt(Utc::now().naive_utc()))
+            .first::<String>(&conn)
+            .optional()?;
+
+        Ok(state.and_then(|state| serde_json::from_str(&state).ok()))
+    }
+
+    fn save(&self, key: &str, state: &SessionState, ttl: i64) -> Result<(), ApiError> {
+        let conn = db::connection()?;
+
+        let state = serde_json::to_string(state)
+            .map_err(|e| ApiError::new(500, format!("Failed to serialize session: {}", e)))?;
+        let expires_at = Utc::now().naive_utc() + Duration::seconds(ttl);
+
+        diesel::insert_into(session::table)
+            .values((
+                session::id.eq(key),
+                session::state.eq(&state),
+                session::expires_at.eq(expires_at),
+            ))
+            .on_conflict(session::id)
+            .do_update()
+            .set((
+                session::state.eq(&state),
+                session::expires_at.eq(expires_at),
+            ))
+            .execute(&conn)?;
+
+        Ok(())
+    }
+
+    fn delete(&self, key: &str) -> Result<(), ApiError> {
+        let conn = db::connection()?;
+
+        diesel::delete(session::table.filter(session::id.eq(key)))
+            .execute(&conn)?;
+
+        Ok(())
+    }
+}
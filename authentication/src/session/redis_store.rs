@@ -0,0 +1,49 @@
+use crate::api_error::ApiError;
+use crate::session::{SessionState, SessionStore};
+use r2d2;
+use redis::{Client, Commands};
+
+type Pool = r2d2::Pool<Client>;
+
+pub struct RedisStore {
+    pool: Pool,
+}
+
+impl RedisStore {
+    pub fn new(redis_url: String) -> Self {
+        let client = Client::open(redis_url.as_str()).expect("Failed to create redis client");
+        let pool = Pool::new(client).expect("Failed to create redis pool");
+        RedisStore { pool }
+    }
+
+    fn connection(&self) -> Result<r2d2::PooledConnection<Client>, ApiError> {
+        self.pool.get()
+            .map_err(|e| ApiError::new(500, format!("Failed getting redis connection: {}", e)))
+    }
+}
+
+impl SessionStore for RedisStore {
+    fn load(&self, key: &str) -> Result<Option<SessionState>, ApiError> {
+        let mut conn = self.connection()?;
+        let res: Option<Vec<u8>> = conn.get(session_key(key))?;
+        Ok(res.and_then(|res| serde_json::from_slice(&res).ok()))
+    }
+
+    fn save(&self, key: &str, state: &SessionState, ttl: i64) -> Result<(), ApiError> {
+        let mut conn = self.connection()?;
+        let value = serde_json::to_vec(state)
+            .map_err(|e| ApiError::new(500, format!("Failed to serialize session: {}", e)))?;
+        let _: () = conn.set_ex(session_key(key), value, ttl as usize)?;
+        Ok(())
+    }
+
+    fn delete(&self, key: &str) -> Result<(), ApiError> {
+        let mut conn = self.connection()?;
+        let _: () = conn.del(session_key(key))?;
+        Ok(())
+    }
+}
+
+fn session_key(key: &str) -> String {
+    format!("session.{}", key)
+}
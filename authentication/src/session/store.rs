@@ -0,0 +1,34 @@
+use crate::api_error::ApiError;
+use crate::session::{MemoryStore, PostgresStore, RedisStore};
+use std::collections::HashMap;
+use std::env;
+use std::sync::Arc;
+
+pub type SessionState = HashMap<String, String>;
+
+pub trait SessionStore: Send + Sync {
+    fn load(&self, key: &str) -> Result<Option<SessionState>, ApiError>;
+    fn save(&self, key: &str, state: &SessionState, ttl: i64) -> Result<(), ApiError>;
+    fn delete(&self, key: &str) -> Result<(), ApiError>;
+}
+
+// Picks the store from SESSION_STORE, which can be either redis, postgres or memory.
+// Has to be called from within the actix runtime.
+pub fn from_env() -> Arc<dyn SessionStore> {
+    let store = env::var("SESSION_STORE").unwrap_or("redis".to_string());
+
+    info!("Initializing {} session store", store);
+    match store.as_str() {
+        "redis" => {
+            let redis_port = env::var("REDIS_PORT").expect("Redis port not set");
+            let redis_host = env::var("REDIS_HOST").expect("Redis host not set");
+            Arc::new(RedisStore::new(format!("redis://{}:{}", redis_host, redis_port)))
+        },
+        "postgres" => {
+            PostgresStore::start_cleanup();
+            Arc::new(PostgresStore)
+        },
+        "memory" => Arc::new(MemoryStore::new()),
+        store => panic!("Unknown session store: {}", store),
+    }
+}
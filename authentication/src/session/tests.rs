@@ -0,0 +1,40 @@
+#[cfg(test)]
+mod tests {
+    use crate::db;
+    use crate::schema::session;
+    use crate::session::{PostgresStore, SessionState, SessionStore};
+    use diesel::prelude::*;
+    use uuid::Uuid;
+
+    fn exists(key: &str) -> bool {
+        let conn = db::connection().unwrap();
+        let count: i64 = session::table
+            .filter(session::id.eq(key))
+            .count()
+            .get_result(&conn)
+            .unwrap();
+        count > 0
+    }
+
+    #[test]
+    fn test_expired_sessions_are_deleted() {
+        crate::test::init();
+
+        let mut state = SessionState::new();
+        state.insert("user_id".to_string(), "\"test\"".to_string());
+
+        let expired = Uuid::new_v4().to_string();
+        let active = Uuid::new_v4().to_string();
+        PostgresStore.save(&expired, &state, -60).unwrap();
+        PostgresStore.save(&active, &state, 60).unwrap();
+
+        assert!(PostgresStore.load(&expired).unwrap().is_none(), "An expired session shouldn't be loaded");
+
+        let deleted = PostgresStore::delete_expired().unwrap();
+        assert!(deleted >= 1);
+        assert!(!exists(&expired), "The expired session should be deleted");
+        assert!(exists(&active), "An active session should be kept");
+
+        PostgresStore.delete(&active).unwrap();
+    }
+}
@@ -1,5 +1,7 @@
 mod model;
+mod password;
 mod routes;
 
 pub use model::*;
+pub use password::{init, PASSWORD_CONFIG};
 pub use routes::init_routes;
@@ -1,7 +1,7 @@
 use crate::api_error::ApiError;
 use crate::db;
 use crate::schema::user;
-use argon2::Config;
+use super::password::{DUMMY_PASSWORD, PASSWORD_CONFIG};
 use chrono::{NaiveDateTime, Utc};
 use diesel::prelude::*;
 use rand::Rng;
@@ -93,7 +93,7 @@ impl User {
 
     pub fn hash_password(&mut self) -> Result<(), ApiError> {
         let salt: [u8; 32] = rand::thread_rng().gen();
-        let config = Config::default();
+        let config = PASSWORD_CONFIG.argon2();
 
         self.password = argon2::hash_encoded(self.password.as_bytes(), &salt, &config)
             .map_err(|e| ApiError::new(500, format!("Failed to hash password: {}", e)))?;
@@ -105,6 +105,33 @@ impl User {
         argon2::verify_encoded(&self.password, password)
             .map_err(|e| ApiError::new(500, format!("Failed to verify password: {}", e)))
     }
+
+    // Hashes created with older argon2 parameters are replaced once we know the password.
+    pub fn rehash_password_if_outdated(&mut self, password: &str) -> Result<(), ApiError> {
+        if PASSWORD_CONFIG.is_current(&self.password) {
+            return Ok(());
+        }
+
+        self.password = password.to_string();
+        self.hash_password()?;
+
+        let conn = db::connection()?;
+        diesel::update(user::table)
+            .filter(user::id.eq(self.id))
+            .set(user::password.eq(&self.password))
+            .execute(&conn)?;
+
+        Ok(())
+    }
+
+    // Runs the same work as verify_password against a throwaway hash, so a
+    // missing user takes as long to reject as a wrong password.
+    pub fn verify_dummy_password(password: &[u8]) -> Result<bool, ApiError> {
+        argon2::verify_encoded(&DUMMY_PASSWORD, password)
+            .map_err(|e| ApiError::new(500, format!("Failed to verify password: {}", e)))?;
+
+        Ok(false)
+    }
 }
 
 impl From<UserMessage> for User {
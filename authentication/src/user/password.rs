@@ -0,0 +1,81 @@
+use argon2::{Config, ThreadMode, Variant, Version};
+use lazy_static::lazy_static;
+use rand::Rng;
+use std::env;
+
+lazy_static! {
+    pub static ref PASSWORD_CONFIG: PasswordConfig = PasswordConfig::from_env();
+
+    // Verified against when there is no user, see User::verify_dummy_password
+    pub static ref DUMMY_PASSWORD: String = {
+        let salt: [u8; 32] = rand::thread_rng().gen();
+        argon2::hash_encoded(b"dummy password", &salt, &PASSWORD_CONFIG.argon2())
+            .expect("Failed to hash dummy password")
+    };
+}
+
+// Hashes the dummy password up front, so the first sign in with an unknown email
+// isn't slower than the others
+pub fn init() {
+    info!("Initializing password hashing");
+    lazy_static::initialize(&PASSWORD_CONFIG);
+    lazy_static::initialize(&DUMMY_PASSWORD);
+}
+
+pub struct PasswordConfig {
+    variant: Variant,
+    mem_cost: u32,
+    time_cost: u32,
+    lanes: u32,
+}
+
+impl PasswordConfig {
+    fn from_env() -> Self {
+        let defaults = Config::default();
+
+        let variant = match env::var("ARGON2_VARIANT") {
+            Ok(variant) => Variant::from_str(&variant).expect("Argon2 variant not valid"),
+            Err(_) => defaults.variant,
+        };
+
+        PasswordConfig {
+            variant,
+            mem_cost: env_u32("ARGON2_MEMORY", defaults.mem_cost),
+            time_cost: env_u32("ARGON2_ITERATIONS", defaults.time_cost),
+            lanes: env_u32("ARGON2_PARALLELISM", defaults.lanes),
+        }
+    }
+
+    pub fn argon2(&self) -> Config<'static> {
+        Config {
+            variant: self.variant,
+            version: Version::Version13,
+            mem_cost: self.mem_cost,
+            time_cost: self.time_cost,
+            lanes: self.lanes,
+            thread_mode: ThreadMode::from_threads(self.lanes),
+            ..Config::default()
+        }
+    }
+
+    // Compares the parameters stored in an encoded hash,
+    // e.g. $argon2i$v=19$m=4096,t=3,p=1$<salt>$<hash>, against the current ones.
+    pub fn is_current(&self, encoded: &str) -> bool {
+        let parts: Vec<&str> = encoded.split('$').collect();
+        if parts.len() != 6 {
+            return false;
+        }
+
+        let params = format!("m={},t={},p={}", self.mem_cost, self.time_cost, self.lanes);
+        let version = format!("v={}", Version::Version13.as_u32());
+
+        parts[1] == self.variant.as_lowercase_str() && parts[2] == version && parts[3] == params
+    }
+}
+
+fn env_u32(key: &str, default: u32) -> u32 {
+    match env::var(key) {
+        Ok(value) => value.parse().unwrap_or_else(|_| panic!("{} must be a positive number", key)),
+        Err(_) => default,
+    }
+}
@@ -1,5 +1,6 @@
 use actix_web::http::StatusCode;
 use actix_web::{HttpResponse, ResponseError};
+use actix_web::error::BlockingError;
 use diesel::result::Error as DieselError;
 use redis::RedisError;
 use serde::Deserialize;
@@ -40,6 +41,15 @@ impl From<RedisError> for ApiError {
     }
 }
 
+impl From<BlockingError<ApiError>> for ApiError {
+    fn from(error: BlockingError<ApiError>) -> ApiError {
+        match error {
+            BlockingError::Error(error) => error,
+            BlockingError::Canceled => ApiError::new(500, "Blocking operation was canceled".to_string()),
+        }
+    }
+}
+
 impl ResponseError for ApiError {
     fn error_response(&self) -> HttpResponse {
         let status_code = match StatusCode::from_u16(self.status_code) {
@@ -0,0 +1,121 @@
+use crate::api_error::ApiError;
+use crate::cache;
+use chrono::Utc;
+use lazy_static::lazy_static;
+use r2d2;
+use redis::{Client, ConnectionLike, RedisResult};
+use std::env;
+use std::sync::atomic::{AtomicI64, AtomicUsize, Ordering};
+use std::time::Duration;
+
+type Pool = r2d2::Pool<Client>;
+pub type CacheConnection = r2d2::PooledConnection<Client>;
+
+const FAILURE_THRESHOLD: usize = 5;
+const OPEN_SECONDS: i64 = 30;
+
+pub(super) struct CircuitBreaker {
+    failures: AtomicUsize,
+    open_until: AtomicI64,
+}
+
+impl CircuitBreaker {
+    pub(super) const fn new() -> Self {
+        CircuitBreaker { failures: AtomicUsize::new(0), open_until: AtomicI64::new(0) }
+    }
+
+    pub(super) fn is_open(&self, now: i64) -> bool {
+        self.open_until.load(Ordering::Relaxed) > now
+    }
+
+    pub(super) fn record_success(&self) {
+        self.failures.store(0, Ordering::Relaxed);
+        self.open_until.store(0, Ordering::Relaxed);
+    }
+
+    // Once the breaker has been open, a single failure is enough to open it again
+    pub(super) fn record_failure(&self, now: i64) {
+        let failures = self.failures.fetch_add(1, Ordering::Relaxed) + 1;
+        if self.open_until.load(Ordering::Relaxed) != 0 || failures >= FAILURE_THRESHOLD {
+            warn!("Cache unavailable, skipping it for {} seconds", OPEN_SECONDS);
+            self.failures.store(0, Ordering::Relaxed);
+            self.open_until.store(now + OPEN_SECONDS, Ordering::Relaxed);
+        }
+    }
+}
+
+static BREAKER: CircuitBreaker = CircuitBreaker::new();
+
+lazy_static! {
+    static ref POOL: Pool = {
+        let redis_url = env::var("REDIS_URL").expect("Redis url not set");
+        let client = redis::Client::open(redis_url).expect("Failed to create redis client");
+        Pool::builder()
+            .connection_timeout(Duration::from_secs(1))
+            .build_unchecked(client)
+    };
+}
+
+pub fn init() {
+    info!("Initializing Cache");
+    lazy_static::initialize(&POOL);
+
+    match connection() {
+        Ok(mut conn) if conn.check_connection() => (),
+        _ => warn!("Redis connection check failed, starting without cache"),
+    }
+
+    cache::listen_for_invalidations();
+}
+
+pub fn connection() -> Result<CacheConnection, ApiError> {
+    POOL.get()
+        .map_err(|e| ApiError::new(500, format!("Failed getting redis connection: {}", e)))
+}
+
+// Runs a cache operation, but treats any failure as a cache miss so that a redis
+// outage doesn't take the API down with it. After repeated failures the cache is
+// skipped entirely for a while, instead of waiting on a dead redis for every request.
+pub fn run<T, F>(operation: F) -> Option<T>
+where
+    F: FnOnce(&mut CacheConnection) -> RedisResult<T>,
+{
+    if BREAKER.is_open(Utc::now().timestamp()) {
+        return None;
+    }
+
+    // Redis can't be trusted until the invalidations it missed have gone through,
+    // so it is skipped while they are replayed in the background
+    if cache::has_pending_invalidations() {
+        cache::replay_invalidations();
+        return None;
+    }
+
+    run_unchecked(operation)
+}
+
+// Like run, but goes to redis even while there are invalidations waiting to be replayed
+pub(super) fn run_unchecked<T, F>(operation: F) -> Option<T>
+where
+    F: FnOnce(&mut CacheConnection) -> RedisResult<T>,
+{
+    let now = Utc::now().timestamp();
+    if BREAKER.is_open(now) {
+        return None;
+    }
+
+    let res = connection()
+        .and_then(|mut conn| operation(&mut conn).map_err(ApiError::from));
+
+    match res {
+        Ok(value) => {
+            BREAKER.record_success();
+            Some(value)
+        },
+        Err(e) => {
+            warn!("Bypassing cache: {}", e);
+            BREAKER.record_failure(now);
+            None
+        },
+    }
+}
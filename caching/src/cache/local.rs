@@ -0,0 +1,108 @@
+use crate::cache;
+use chrono::Utc;
+use lazy_static::lazy_static;
+use lru::LruCache;
+use redis::Commands;
+use std::env;
+use std::sync::{Mutex, PoisonError};
+use std::thread;
+use std::time::Duration;
+use uuid::Uuid;
+
+pub const INVALIDATION_CHANNEL: &str = "cache.invalidate";
+
+struct LocalEntry {
+    value: Vec<u8>,
+    expires_at: i64,
+}
+
+lazy_static! {
+    static ref LOCAL: Mutex<LruCache<String, LocalEntry>> = {
+        let capacity = env::var("CACHE_LOCAL_CAPACITY")
+            .map(|capacity| capacity.parse().expect("Cache local capacity must be a positive number"))
+            .unwrap_or(1000);
+        Mutex::new(LruCache::new(capacity))
+    };
+
+    // Invalidations can be delivered late or lost while the subscriber reconnects,
+    // so local entries are only trusted for a short while.
+    static ref LOCAL_TTL: i64 = env::var("CACHE_LOCAL_TTL")
+        .map(|ttl| ttl.parse().expect("Cache local ttl must be a positive number"))
+        .unwrap_or(30);
+
+    // Lets an instance ignore its own invalidations
+    static ref INSTANCE_ID: String = Uuid::new_v4().to_string();
+}
+
+pub fn local_get(key: &str) -> Option<Vec<u8>> {
+    let mut local = LOCAL.lock().unwrap_or_else(PoisonError::into_inner);
+
+    match local.get(key) {
+        Some(entry) if entry.expires_at > Utc::now().timestamp_millis() => Some(entry.value.clone()),
+        Some(_) => {
+            local.pop(key);
+            None
+        },
+        None => None,
+    }
+}
+
+pub fn local_set(key: &str, value: Vec<u8>, expires_at: i64) {
+    let expires_at = expires_at.min(Utc::now().timestamp_millis() + *LOCAL_TTL * 1000);
+    LOCAL.lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .put(key.to_string(), LocalEntry { value, expires_at });
+}
+
+pub(super) fn local_remove(key: &str) {
+    LOCAL.lock().unwrap_or_else(PoisonError::into_inner).pop(key);
+}
+
+// Drops the key here and tells the other instances to do the same
+pub fn invalidate(key: &str) {
+    local_remove(key);
+    publish_invalidation(key);
+}
+
+// Tells the other instances to drop their copy of the key
+pub fn publish_invalidation(key: &str) {
+    cache::run(|cache| cache.publish::<_, _, ()>(INVALIDATION_CHANNEL, invalidation_message(key)));
+}
+
+pub(super) fn invalidation_message(key: &str) -> String {
+    format!("{} {}", *INSTANCE_ID, key)
+}
+
+pub fn listen_for_invalidations() {
+    let redis_url = env::var("REDIS_URL").expect("Redis url not set");
+    let client = redis::Client::open(redis_url).expect("Failed to create redis client");
+
+    thread::spawn(move || loop {
+        if let Err(e) = subscribe(&client) {
+            warn!("Cache invalidation subscription failed: {}", e);
+        }
+
+        thread::sleep(Duration::from_secs(5));
+    });
+}
+
+fn subscribe(client: &redis::Client) -> redis::RedisResult<()> {
+    let mut conn = client.get_connection()?;
+    let mut pubsub = conn.as_pubsub();
+    pubsub.subscribe(INVALIDATION_CHANNEL)?;
+
+    // Anything published while we weren't subscribed is lost
+    LOCAL.lock().unwrap_or_else(PoisonError::into_inner).clear();
+
+    loop {
+        let message: String = pubsub.get_message()?.get_payload()?;
+        let mut parts = message.splitn(2, ' ');
+
+        match (parts.next(), parts.next()) {
+            (Some(instance_id), Some(key)) if instance_id != *INSTANCE_ID => {
+                LOCAL.lock().unwrap_or_else(PoisonError::into_inner).pop(key);
+            },
+            _ => (),
+        }
+    }
+}
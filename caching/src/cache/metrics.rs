@@ -0,0 +1,63 @@
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+struct Counters {
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl Counters {
+    const fn new() -> Self {
+        Counters { hits: AtomicU64::new(0), misses: AtomicU64::new(0) }
+    }
+
+    fn record(&self, hit: bool) {
+        match hit {
+            true => self.hits.fetch_add(1, Ordering::Relaxed),
+            false => self.misses.fetch_add(1, Ordering::Relaxed),
+        };
+    }
+
+    fn snapshot(&self) -> TierMetrics {
+        let hits = self.hits.load(Ordering::Relaxed);
+        let misses = self.misses.load(Ordering::Relaxed);
+        let hit_rate = match hits + misses {
+            0 => 0.0,
+            total => hits as f64 / total as f64,
+        };
+
+        TierMetrics { hits, misses, hit_rate }
+    }
+}
+
+static LOCAL: Counters = Counters::new();
+static REDIS: Counters = Counters::new();
+
+#[derive(Serialize)]
+pub struct TierMetrics {
+    pub hits: u64,
+    pub misses: u64,
+    pub hit_rate: f64,
+}
+
+#[derive(Serialize)]
+pub struct CacheMetrics {
+    pub local: TierMetrics,
+    pub redis: TierMetrics,
+}
+
+pub fn record_local(hit: bool) {
+    LOCAL.record(hit);
+}
+
+// Only lookups that missed the local tier reach redis
+pub fn record_redis(hit: bool) {
+    REDIS.record(hit);
+}
+
+pub fn metrics() -> CacheMetrics {
+    CacheMetrics {
+        local: LOCAL.snapshot(),
+        redis: REDIS.snapshot(),
+    }
+}
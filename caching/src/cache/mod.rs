@@ -0,0 +1,18 @@
+mod connection;
+mod local;
+mod metrics;
+mod pending;
+mod repository;
+mod routes;
+mod serializer;
+mod single_flight;
+mod tests;
+
+pub use connection::*;
+pub use local::*;
+pub use metrics::*;
+pub use pending::*;
+pub use repository::*;
+pub use routes::init_routes;
+pub use serializer::*;
+pub use single_flight::*;
@@ -0,0 +1,106 @@
+use crate::cache::{self, INVALIDATION_CHANNEL};
+use lazy_static::lazy_static;
+use redis::Commands;
+use std::collections::HashSet;
+use std::sync::{Mutex, PoisonError};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+
+// Beyond this, missed deletes are dropped and those entries are left to expire with their ttl
+const MAX_PENDING: usize = 10_000;
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Invalidation {
+    Delete(String),
+    Increment(String),
+}
+
+static HAS_PENDING: AtomicBool = AtomicBool::new(false);
+static REPLAYING: AtomicBool = AtomicBool::new(false);
+
+lazy_static! {
+    static ref PENDING: Mutex<HashSet<Invalidation>> = Mutex::new(HashSet::new());
+}
+
+// An invalidation that can't reach redis is kept and replayed once redis answers
+// again. Otherwise a write made during an outage would leave the old entry in
+// redis, to be served for the rest of its ttl after the cache comes back.
+pub fn invalidate_or_defer(invalidation: Invalidation) {
+    let res = match &invalidation {
+        Invalidation::Delete(key) => cache::run(|cache| cache.del::<_, ()>(key)),
+        Invalidation::Increment(key) => cache::run(|cache| cache.incr::<_, _, ()>(key, 1)),
+    };
+
+    if res.is_none() {
+        defer(invalidation);
+    }
+}
+
+pub fn defer(invalidation: Invalidation) {
+    let mut pending = PENDING.lock().unwrap_or_else(PoisonError::into_inner);
+    if pending.len() >= MAX_PENDING && !pending.contains(&invalidation) {
+        warn!("Too many pending cache invalidations, dropping {:?}", invalidation);
+        return;
+    }
+
+    pending.insert(invalidation);
+    HAS_PENDING.store(true, Ordering::Relaxed);
+}
+
+// Stays true until the last pending invalidation has reached redis
+pub fn has_pending_invalidations() -> bool {
+    HAS_PENDING.load(Ordering::Relaxed)
+}
+
+// There can be thousands of them after a long outage, so they are replayed on a
+// thread of their own rather than in the request that noticed redis was back
+pub fn replay_invalidations() {
+    if REPLAYING.swap(true, Ordering::Relaxed) {
+        return;
+    }
+
+    thread::spawn(|| {
+        replay();
+        REPLAYING.store(false, Ordering::Relaxed);
+    });
+}
+
+fn replay() {
+    info!("Replaying pending cache invalidations");
+
+    loop {
+        let invalidation = {
+            let mut pending = PENDING.lock().unwrap_or_else(PoisonError::into_inner);
+            match pending.iter().next().cloned() {
+                Some(invalidation) => {
+                    pending.remove(&invalidation);
+                    invalidation
+                },
+                None => {
+                    HAS_PENDING.store(false, Ordering::Relaxed);
+                    return;
+                },
+            }
+        };
+
+        if !apply(&invalidation) {
+            // Redis went away again, the next request will start over
+            defer(invalidation);
+            return;
+        }
+    }
+}
+
+fn apply(invalidation: &Invalidation) -> bool {
+    match invalidation {
+        Invalidation::Delete(key) => {
+            // Other instances may still have the old value in their local tier
+            cache::local_remove(key);
+            cache::run_unchecked(|cache| {
+                cache.del::<_, ()>(key)?;
+                cache.publish::<_, _, ()>(INVALIDATION_CHANNEL, cache::invalidation_message(key))
+            }).is_some()
+        },
+        Invalidation::Increment(key) => cache::run_unchecked(|cache| cache.incr::<_, _, ()>(key, 1)).is_some(),
+    }
+}
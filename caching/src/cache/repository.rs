@@ -0,0 +1,244 @@
+use crate::api_error::ApiError;
+use crate::cache::{self, Invalidation, Serializer};
+use chrono::Utc;
+use redis::Commands;
+use serde::{Deserialize, Serialize};
+use serde::de::DeserializeOwned;
+use sha2::{Digest, Sha256};
+use std::env;
+use std::fmt::Display;
+use std::time::Instant;
+
+// Higher values make entries refresh earlier
+const EARLY_REFRESH_BETA: f64 = 1.0;
+
+pub struct CacheConfig {
+    pub prefix: &'static str,
+    pub ttl: usize,
+    pub list_ttl: usize,
+    pub negative_ttl: usize,
+    pub serializer: Serializer,
+}
+
+impl CacheConfig {
+    // The defaults can be overridden with CACHE_<PREFIX>_TTL, CACHE_<PREFIX>_LIST_TTL,
+    // CACHE_<PREFIX>_NEGATIVE_TTL and CACHE_SERIALIZER
+    pub fn from_env(prefix: &'static str, ttl: usize, list_ttl: usize) -> Self {
+        let ttl = env_ttl(&format!("CACHE_{}_TTL", prefix.to_uppercase()), ttl);
+        let list_ttl = env_ttl(&format!("CACHE_{}_LIST_TTL", prefix.to_uppercase()), list_ttl);
+        let negative_ttl = env_ttl(&format!("CACHE_{}_NEGATIVE_TTL", prefix.to_uppercase()), 10);
+
+        let serializer = match env::var("CACHE_SERIALIZER") {
+            Ok(name) => Serializer::from_name(&name).expect("Cache serializer must be json, msgpack or bincode"),
+            Err(_) => Serializer::Json,
+        };
+
+        CacheConfig { prefix, ttl, list_ttl, negative_ttl, serializer }
+    }
+
+    pub fn key<K: Display>(&self, key: K) -> String {
+        format!("{}.{}", self.prefix, key)
+    }
+
+    fn list_generation_key(&self) -> String {
+        format!("{}.list.generation", self.prefix)
+    }
+
+    // Lists are cached per generation, so bumping the generation makes every
+    // cached list unreachable at once. The old entries are left to expire.
+    fn list_key(&self, generation: u64, params: &[u8]) -> String {
+        format!("{}.list.{}.{}", self.prefix, generation, hex::encode(Sha256::digest(params)))
+    }
+}
+
+// A missing value is cached too, so repeated lookups of something that doesn't
+// exist don't all go to the database.
+#[derive(Serialize, Deserialize)]
+struct CacheEntry<T> {
+    value: Option<T>,
+    // How long the value took to load, in milliseconds
+    delta: i64,
+    expires_at: i64,
+}
+
+impl<T> CacheEntry<T> {
+    fn new(value: Option<T>, delta: i64, ttl: usize) -> Self {
+        let expires_at = Utc::now().timestamp_millis() + ttl as i64 * 1000;
+        CacheEntry { value, delta, expires_at }
+    }
+
+    // Probabilistic early expiration. Each reader is more likely to refresh the
+    // entry the closer it is to expiring and the slower it is to load, so one
+    // of them usually reloads it before everyone misses at once.
+    fn should_refresh(&self) -> bool {
+        let now = Utc::now().timestamp_millis() as f64;
+        let gap = self.delta as f64 * EARLY_REFRESH_BETA * rand::random::<f64>().ln();
+        now - gap >= self.expires_at as f64
+    }
+
+    fn into_result(self) -> Result<T, ApiError> {
+        self.value.ok_or_else(|| ApiError::new(404, "Record not found".to_string()))
+    }
+}
+
+fn env_ttl(key: &str, default: usize) -> usize {
+    match env::var(key) {
+        Ok(ttl) => ttl.parse().expect("Cache ttl must be a positive number"),
+        Err(_) => default,
+    }
+}
+
+// Cache-aside for any serializable resource. Implementors only say how they are
+// keyed and configured, the lookups and invalidation come for free.
+pub trait CacheRepository: Serialize + DeserializeOwned {
+    type Key: Display;
+
+    fn cache_config() -> &'static CacheConfig;
+
+    fn cache_key(&self) -> Self::Key;
+
+    fn cache_find(key: &Self::Key) -> Option<Self> {
+        cache_entry::<Self>(&Self::cache_config().key(key))?.value
+    }
+
+    // If redis is down the old entry is removed once it's back, instead of being overwritten
+    fn cache_set(&self) {
+        let config = Self::cache_config();
+        let key = config.key(self.cache_key());
+        match cache_store(config, &key, Some(self), 0, config.ttl) {
+            // Other instances drop their copy and pick up the new one from redis
+            true => cache::publish_invalidation(&key),
+            false => {
+                cache::defer(Invalidation::Delete(key.clone()));
+                cache::invalidate(&key);
+            },
+        }
+    }
+
+    fn cache_delete(key: &Self::Key) {
+        let key = Self::cache_config().key(key);
+        cache::invalidate_or_defer(Invalidation::Delete(key.clone()));
+        cache::invalidate(&key);
+    }
+
+    // The params should be normalized by the caller, so that equivalent queries share a cache entry
+    fn cached_list<P, F>(params: &P, load: F) -> Result<Vec<Self>, ApiError>
+    where
+        P: Serialize,
+        F: FnOnce() -> Result<Vec<Self>, ApiError>,
+    {
+        let config = Self::cache_config();
+        let params = serde_json::to_vec(params)
+            .map_err(|e| ApiError::new(500, format!("Failed to serialize list params: {}", e)))?;
+
+        // The generation has to be read before loading from the database. A write that lands
+        // after this has bumped the generation, so what we store can't be served after it.
+        let generation: Option<Option<u64>> = cache::run(|cache| cache.get(config.list_generation_key()));
+
+        let list_key = match generation {
+            Some(generation) => config.list_key(generation.unwrap_or(0), &params),
+            None => return load(),
+        };
+
+        let res: Option<Option<Vec<u8>>> = cache::run(|cache| cache.get(&list_key));
+        if let Some(values) = res.and_then(|res| res).and_then(|res| config.serializer.deserialize(&res).ok()) {
+            return Ok(values);
+        }
+
+        let values = load()?;
+
+        if let Ok(value) = config.serializer.serialize(&values) {
+            cache::run(|cache| cache.set_ex::<_, _, ()>(&list_key, value, config.list_ttl));
+        }
+
+        Ok(values)
+    }
+
+    fn cache_invalidate_lists() {
+        let config = Self::cache_config();
+        cache::invalidate_or_defer(Invalidation::Increment(config.list_generation_key()));
+    }
+
+    // Concurrent misses for the same key are coalesced, and not found errors are
+    // cached for a short while
+    fn cached<F>(key: &Self::Key, load: F) -> Result<Self, ApiError>
+    where
+        F: FnOnce() -> Result<Self, ApiError>,
+    {
+        let config = Self::cache_config();
+        let cache_key = config.key(key);
+
+        match cache_entry::<Self>(&cache_key) {
+            Some(entry) if !entry.should_refresh() => return entry.into_result(),
+            _ => (),
+        }
+
+        cache::single_flight(&cache_key, || {
+            // Another caller may have loaded it while we were waiting
+            match cache_entry::<Self>(&cache_key) {
+                Some(entry) if !entry.should_refresh() => return entry.into_result(),
+                _ => (),
+            }
+
+            let started = Instant::now();
+            let res = load();
+            let delta = started.elapsed().as_millis() as i64;
+
+            match res {
+                Ok(value) => {
+                    cache_store(config, &cache_key, Some(&value), delta, config.ttl);
+                    Ok(value)
+                },
+                Err(e) if e.status_code == 404 => {
+                    cache_store::<Self>(config, &cache_key, None, delta, config.negative_ttl);
+                    Err(e)
+                },
+                Err(e) => Err(e),
+            }
+        })
+    }
+}
+
+// Looks in the local tier first, then in redis
+fn cache_entry<T: CacheRepository>(key: &str) -> Option<CacheEntry<T>> {
+    let config = T::cache_config();
+
+    if let Some(bytes) = cache::local_get(key) {
+        cache::record_local(true);
+        return config.serializer.deserialize(&bytes).ok();
+    }
+    cache::record_local(false);
+
+    let res: Option<Option<Vec<u8>>> = cache::run(|cache| cache.get(key));
+    let bytes = match res.and_then(|res| res) {
+        Some(bytes) => bytes,
+        None => {
+            cache::record_redis(false);
+            return None;
+        },
+    };
+    cache::record_redis(true);
+
+    let entry: CacheEntry<T> = config.serializer.deserialize(&bytes).ok()?;
+    cache::local_set(key, bytes, entry.expires_at);
+
+    Some(entry)
+}
+
+// Returns whether redis has the new entry
+fn cache_store<T: Serialize>(config: &CacheConfig, key: &str, value: Option<&T>, delta: i64, ttl: usize) -> bool {
+    let entry = CacheEntry::new(value, delta, ttl);
+    let value = match config.serializer.serialize(&entry) {
+        Ok(value) => value,
+        Err(e) => {
+            warn!("{}", e);
+            return false;
+        },
+    };
+
+    let stored = cache::run(|cache| cache.set_ex::<_, _, ()>(key, value.clone(), ttl)).is_some();
+
+    cache::local_set(key, value, entry.expires_at);
+
+    stored
+}
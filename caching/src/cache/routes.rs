@@ -0,0 +1,11 @@
+use crate::cache;
+use actix_web::{get, web, HttpResponse};
+
+#[get("/metrics/cache")]
+async fn metrics() -> HttpResponse {
+    HttpResponse::Ok().json(cache::metrics())
+}
+
+pub fn init_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(metrics);
+}
@@ -0,0 +1,41 @@
+use crate::api_error::ApiError;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+#[derive(Clone, Copy, Debug)]
+pub enum Serializer {
+    Json,
+    MessagePack,
+    Bincode,
+}
+
+impl Serializer {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "json" => Some(Serializer::Json),
+            "msgpack" => Some(Serializer::MessagePack),
+            "bincode" => Some(Serializer::Bincode),
+            _ => None,
+        }
+    }
+
+    pub fn serialize<T: Serialize>(&self, value: &T) -> Result<Vec<u8>, ApiError> {
+        let res = match self {
+            Serializer::Json => serde_json::to_vec(value).map_err(|e| e.to_string()),
+            Serializer::MessagePack => rmp_serde::to_vec_named(value).map_err(|e| e.to_string()),
+            Serializer::Bincode => bincode::serialize(value).map_err(|e| e.to_string()),
+        };
+
+        res.map_err(|e| ApiError::new(500, format!("Failed to serialize cache value: {}", e)))
+    }
+
+    pub fn deserialize<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, ApiError> {
+        let res = match self {
+            Serializer::Json => serde_json::from_slice(bytes).map_err(|e| e.to_string()),
+            Serializer::MessagePack => rmp_serde::from_read_ref(bytes).map_err(|e| e.to_string()),
+            Serializer::Bincode => bincode::deserialize(bytes).map_err(|e| e.to_string()),
+        };
+
+        res.map_err(|e| ApiError::new(500, format!("Failed to deserialize cache value: {}", e)))
+    }
+}
@@ -0,0 +1,32 @@
+use lazy_static::lazy_static;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, PoisonError};
+
+lazy_static! {
+    static ref IN_FLIGHT: Mutex<HashMap<String, Arc<Mutex<()>>>> = Mutex::new(HashMap::new());
+}
+
+// Runs one operation per key at a time within this process. Callers that arrive
+// while a load is running wait for it, and should check the cache again before
+// doing their own load. Waiting blocks the thread, so this must not be called
+// directly from an async handler.
+pub fn single_flight<T, F: FnOnce() -> T>(key: &str, operation: F) -> T {
+    let lock = IN_FLIGHT.lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .entry(key.to_string())
+        .or_default()
+        .clone();
+
+    let res = {
+        let _guard = lock.lock().unwrap_or_else(PoisonError::into_inner);
+        operation()
+    };
+
+    // The map holds one reference and we hold another, anything more is a waiter
+    let mut in_flight = IN_FLIGHT.lock().unwrap_or_else(PoisonError::into_inner);
+    if Arc::strong_count(&lock) == 2 {
+        in_flight.remove(key);
+    }
+
+    res
+}
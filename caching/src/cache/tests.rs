@@ -0,0 +1,91 @@
+
+#[cfg(test)]
+mod tests {
+    use crate::cache::{self, Invalidation, Serializer};
+    use crate::cache::connection::CircuitBreaker;
+    use crate::cache::pending;
+    use redis::{Commands, ErrorKind, RedisError};
+    use serde::{Deserialize, Serialize};
+    use uuid::Uuid;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Value {
+        id: u32,
+        name: Option<String>,
+    }
+
+    #[test]
+    fn test_serializers_round_trip() {
+        let value = Value { id: 1, name: Some("Tore".to_string()) };
+
+        for name in &["json", "msgpack", "bincode"] {
+            let serializer = Serializer::from_name(name).unwrap();
+            let bytes = serializer.serialize(&value).unwrap();
+            let res: Value = serializer.deserialize(&bytes).unwrap();
+            assert_eq!(res, value, "{} didn't round trip", name);
+        }
+
+        assert!(Serializer::from_name("xml").is_none());
+        assert!(Serializer::Json.deserialize::<Value>(b"not json").is_err());
+    }
+
+    #[test]
+    fn test_circuit_breaker() {
+        let breaker = CircuitBreaker::new();
+        let now = 1000;
+
+        for _ in 0..4 {
+            breaker.record_failure(now);
+        }
+        assert!(!breaker.is_open(now), "A few failures shouldn't open the breaker");
+
+        breaker.record_failure(now);
+        assert!(breaker.is_open(now), "Repeated failures should open the breaker");
+        assert!(breaker.is_open(now + 29));
+        assert!(!breaker.is_open(now + 30), "The breaker should let a request through after a while");
+
+        // The first request after the wait decides whether it stays open
+        breaker.record_failure(now + 30);
+        assert!(breaker.is_open(now + 31), "One failure should open it again");
+
+        breaker.record_success();
+        assert!(!breaker.is_open(now + 31), "A success should close it");
+        breaker.record_failure(now + 31);
+        assert!(!breaker.is_open(now + 31), "The failure count should start over after closing");
+    }
+
+    #[test]
+    fn test_errors_are_bypassed() {
+        crate::test::init();
+
+        let res: Option<()> = cache::run(|_| Err(RedisError::from((ErrorKind::IoError, "Connection refused"))));
+        assert!(res.is_none(), "A cache error should be treated as a miss");
+    }
+
+    #[test]
+    fn test_invalidations_are_replayed_after_outage() {
+        crate::test::init();
+
+        let key = format!("test.{}", Uuid::new_v4());
+        let generation_key = format!("test.{}.generation", Uuid::new_v4());
+        let mut conn = cache::connection().expect("Redis must be running for this test");
+        conn.set_ex::<_, _, ()>(&key, "stale", 60).unwrap();
+
+        // What a write would leave behind if redis couldn't be reached
+        pending::defer(Invalidation::Delete(key.clone()));
+        pending::defer(Invalidation::Increment(generation_key.clone()));
+
+        let res: Option<Option<String>> = cache::run(|cache| cache.get(&key));
+        assert!(res.is_none(), "Redis should be skipped until the invalidations are replayed");
+
+        crate::test::wait_for_cache_replay();
+
+        let value: Option<String> = conn.get(&key).unwrap();
+        assert_eq!(value, None, "The stale entry should be deleted once redis is back");
+
+        let generation: Option<u64> = conn.get(&generation_key).unwrap();
+        assert_eq!(generation, Some(1), "The missed list invalidation should be replayed");
+
+        conn.del::<_, ()>(&generation_key).unwrap();
+    }
+}
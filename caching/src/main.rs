@@ -16,6 +16,9 @@ mod cache;
 mod schema;
 mod user;
 
+#[cfg(test)]
+mod test;
+
 #[actix_rt::main]
 async fn main() -> std::io::Result<()> {
     dotenv().ok();
@@ -27,6 +30,7 @@ async fn main() -> std::io::Result<()> {
     let mut listenfd = ListenFd::from_env();
     let mut server = HttpServer::new(|| 
         App::new()
+            .configure(cache::init_routes)
             .configure(user::init_routes)
     );
 
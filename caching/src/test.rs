@@ -0,0 +1,32 @@
+use crate::{cache, db};
+use lazy_static::lazy_static;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use dotenv::dotenv;
+
+lazy_static! {
+    static ref INITIATED: Arc<Mutex<bool>> = Arc::new(Mutex::new(false));
+}
+
+#[cfg(test)]
+pub fn init() {
+    let mut initiated = INITIATED.lock().unwrap();
+    if *initiated == false {
+        dotenv().ok();
+        db::init();
+        cache::init();
+        *initiated = true;
+    }
+}
+
+// Waits for the invalidations deferred during a simulated outage to reach redis
+#[cfg(test)]
+pub fn wait_for_cache_replay() {
+    let started = Instant::now();
+    while cache::has_pending_invalidations() {
+        assert!(started.elapsed() < Duration::from_secs(5), "Pending cache invalidations were never replayed");
+        cache::replay_invalidations();
+        thread::sleep(Duration::from_millis(10));
+    }
+}
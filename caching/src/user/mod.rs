@@ -1,5 +1,6 @@
 mod model;
 mod routes;
+mod tests;
 
 pub use model::*;
 pub use routes::init_routes;
@@ -1,13 +1,17 @@
 use crate::api_error::ApiError;
-use crate::cache;
+use crate::cache::{CacheConfig, CacheRepository};
 use crate::db;
 use crate::schema::user;
 use chrono::{NaiveDateTime, Utc};
 use diesel::prelude::*;
+use lazy_static::lazy_static;
 use serde::{Deserialize, Serialize};
-use redis::Commands;
 use uuid::Uuid;
 
+lazy_static! {
+    static ref USER_CACHE: CacheConfig = CacheConfig::from_env("user", 3600, 60);
+}
+
 #[derive(Serialize, Deserialize, AsChangeset)]
 #[table_name = "user"]
 pub struct UserMessage {
@@ -15,6 +19,44 @@ pub struct UserMessage {
     pub password: String,
 }
 
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Params {
+    pub email: Option<String>,
+    pub sort_by: Option<String>,
+    pub page: Option<i64>,
+    pub page_size: Option<i64>,
+}
+
+impl Params {
+    // Fills in the defaults, so that equivalent queries end up with the same cache key
+    pub fn normalize(self) -> Self {
+        let email = self.email
+            .map(|email| email.trim().to_lowercase())
+            .filter(|email| !email.is_empty());
+
+        let sort_by = self.sort_by
+            .map(|sort_by| sort_by.trim().to_lowercase())
+            .filter(|sort_by| !sort_by.is_empty())
+            .unwrap_or_else(|| "id".to_string());
+
+        // Without any paging params every user is returned, as before paging was added
+        let (page, page_size) = match (self.page, self.page_size) {
+            (None, None) => (None, None),
+            (page, page_size) => (
+                Some(page.unwrap_or(1).max(1)),
+                Some(page_size.unwrap_or(10).max(1).min(100)),
+            ),
+        };
+
+        Params { email, sort_by: Some(sort_by), page, page_size }
+    }
+}
+
+// Postgres uses backslash as the default escape character for like patterns
+fn escape_like(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+}
+
 #[derive(Serialize, Deserialize, Queryable, Insertable)]
 #[table_name = "user"]
 pub struct User {
@@ -26,28 +68,47 @@ pub struct User {
 }
 
 impl User {
-    pub fn find_all() -> Result<Vec<Self>, ApiError> {
-        let conn = db::connection()?;
+    pub fn find_all(params: Params) -> Result<Vec<Self>, ApiError> {
+        let params = params.normalize();
 
-        let users = user::table
-            .load::<User>(&conn)?;
+        User::cached_list(&params, || {
+            let conn = db::connection()?;
 
-        Ok(users)
-    }
+            let mut query = user::table.into_boxed();
 
-    pub fn find(id: Uuid) -> Result<Self, ApiError> {
-        if let Some(user) = User::cache_find(id)? {
-            return Ok(user);
-        }
+            if let Some(email) = &params.email {
+                query = query.filter(user::email.ilike(format!("%{}%", escape_like(email))));
+            }
 
-        let conn = db::connection()?;
-        let user = user::table
-            .filter(user::id.eq(id))
-            .first::<User>(&conn)?;
+            query = match params.sort_by.as_deref() {
+                Some("email") => query.order(user::email.asc()),
+                Some("-email") => query.order(user::email.desc()),
+                Some("created_at") => query.order(user::created_at.asc()),
+                Some("-created_at") => query.order(user::created_at.desc()),
+                _ => query.order(user::id.asc()),
+            };
 
-        user.cache_set()?;
+            if let (Some(page), Some(page_size)) = (params.page, params.page_size) {
+                query = query
+                    .limit(page_size)
+                    .offset((page - 1) * page_size);
+            }
 
-        Ok(user)
+            let users = query.load::<User>(&conn)?;
+
+            Ok(users)
+        })
+    }
+
+    pub fn find(id: Uuid) -> Result<Self, ApiError> {
+        User::cached(&id, || {
+            let conn = db::connection()?;
+            let user = user::table
+                .filter(user::id.eq(id))
+                .first::<User>(&conn)?;
+
+            Ok(user)
+        })
     }
 
     pub fn create(user: UserMessage) -> Result<Self, ApiError> {
@@ -56,7 +117,10 @@ impl User {
         let user = User::from(user);
         let user = diesel::insert_into(user::table)
             .values(user)
-            .get_result(&conn)?;
+            .get_result::<User>(&conn)?;
+
+        user.cache_set();
+        User::cache_invalidate_lists();
 
         Ok(user)
     }
@@ -68,7 +132,8 @@ impl User {
             .set(user)
             .get_result::<User>(&conn)?;
 
-        user.cache_set()?;
+        user.cache_set();
+        User::cache_invalidate_lists();
 
         Ok(user)
     }
@@ -81,35 +146,22 @@ impl User {
             )
             .execute(&conn)?;
 
-        User::cache_delete(id)?;
+        User::cache_delete(&id);
+        User::cache_invalidate_lists();
 
         Ok(res)
     }
+}
 
-    fn cache_find(id: Uuid) -> Result<Option<Self>, ApiError> {
-        let cache_key = format!("user.{}", id);
-        let mut cache = cache::connection()?;
-        let res: Vec<u8> = cache.get(&cache_key)?;
-        match serde_json::from_slice::<User>(&res).ok() {
-            Some(user) => Ok(Some(user)),
-            None => Ok(None),
-        }
-    }
+impl CacheRepository for User {
+    type Key = Uuid;
 
-    fn cache_set(&self) -> Result<(), ApiError> {
-        let cache_key = format!("user.{}", self.id);
-        let mut cache = cache::connection()?;
-        if let Some(cache_user) = serde_json::to_vec(self).ok() {
-            let _: () = cache.set_ex(&cache_key, cache_user, 3600)?;
-        }
-        Ok(())
+    fn cache_config() -> &'static CacheConfig {
+        &USER_CACHE
     }
 
-    fn cache_delete(id: Uuid) -> Result<(), ApiError> {
-        let cache_key = format!("user.{}", id);
-        let mut cache = cache::connection()?;
-        let _: () = cache.del(cache_key)?;
-        Ok(())
+    fn cache_key(&self) -> Uuid {
+        self.id
     }
 }
 
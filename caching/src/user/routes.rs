@@ -1,36 +1,39 @@
 use crate::api_error::ApiError;
-use crate::user::{User, UserMessage};
+use crate::user::{Params, User, UserMessage};
 use actix_web::{delete, get, post, put, web, HttpResponse};
 use serde_json::json;
 use uuid::Uuid;
 
+// Lookups can wait on another request loading the same key, and every handler can
+// wait on redis and postgres, so they run on the blocking thread pool instead of
+// holding up an actix worker
 #[get("/users")]
-async fn find_all() -> Result<HttpResponse, ApiError> {
-    let users = User::find_all()?;
+async fn find_all(params: web::Query<Params>) -> Result<HttpResponse, ApiError> {
+    let users = web::block(move || User::find_all(params.into_inner())).await?;
     Ok(HttpResponse::Ok().json(users))
 }
 
 #[get("/users/{id}")]
 async fn find(id: web::Path<Uuid>) -> Result<HttpResponse, ApiError> {
-    let user = User::find(id.into_inner())?;
+    let user = web::block(move || User::find(id.into_inner())).await?;
     Ok(HttpResponse::Ok().json(user))
 }
 
 #[post("/users")]
 async fn create(user: web::Json<UserMessage>) -> Result<HttpResponse, ApiError> {
-    let user = User::create(user.into_inner())?;
+    let user = web::block(move || User::create(user.into_inner())).await?;
     Ok(HttpResponse::Ok().json(user))
 }
 
 #[put("/users/{id}")]
 async fn update(id: web::Path<Uuid>, user: web::Json<UserMessage>) -> Result<HttpResponse, ApiError> {
-    let user = User::update(id.into_inner(), user.into_inner())?;
+    let user = web::block(move || User::update(id.into_inner(), user.into_inner())).await?;
     Ok(HttpResponse::Ok().json(user))
 }
 
 #[delete("/users/{id}")]
 async fn delete(id: web::Path<Uuid>) -> Result<HttpResponse, ApiError> {
-    let num_deleted = User::delete(id.into_inner())?;
+    let num_deleted = web::block(move || User::delete(id.into_inner())).await?;
     Ok(HttpResponse::Ok().json(json!({ "deleted": num_deleted })))
 }
 
@@ -0,0 +1,232 @@
+
+#[cfg(test)]
+mod tests {
+    use crate::user::*;
+    use actix_web::{test::{self, TestRequest}, App};
+    use crate::api_error::ApiError;
+    use crate::cache::{self, CacheRepository, Invalidation};
+    use crate::db;
+    use crate::schema::user;
+    use diesel::prelude::*;
+    use serde_json::json;
+    use std::env;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+    use std::time::Duration;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_params_normalize() {
+        let params = Params {
+            email: Some(" Tore@Cloudmaker.dev ".to_string()),
+            sort_by: None,
+            page: Some(0),
+            page_size: Some(1000),
+        }.normalize();
+
+        assert_eq!(params.email.as_deref(), Some("tore@cloudmaker.dev"));
+        assert_eq!(params.sort_by.as_deref(), Some("id"));
+        assert_eq!(params.page, Some(1));
+        assert_eq!(params.page_size, Some(100));
+
+        let a = serde_json::to_string(&Params { page_size: Some(10), ..Params::default() }.normalize()).unwrap();
+        let b = serde_json::to_string(&Params { page: Some(1), ..Params::default() }.normalize()).unwrap();
+        assert_eq!(a, b, "Equivalent params should normalize to the same value");
+
+        let params = Params::default().normalize();
+        assert_eq!((params.page, params.page_size), (None, None), "No paging params should mean no paging");
+    }
+
+    #[actix_rt::test]
+    async fn test_find_all_pages_only_when_asked() {
+        crate::test::init();
+
+        let domain = format!("{}.cloudmaker.dev", Uuid::new_v4());
+        let users: Vec<User> = (0..11)
+            .map(|i| User::create(UserMessage { email: format!("{}@{}", i, domain), password: "test".to_string() }).unwrap())
+            .collect();
+
+        let mut app = test::init_service(App::new().configure(init_routes)).await;
+
+        let resp = TestRequest::get().uri(&format!("/users?email={}", domain)).send_request(&mut app).await;
+        let found: Vec<User> = test::read_body_json(resp).await;
+        assert_eq!(found.len(), 11, "Without paging params every user should be returned");
+
+        let resp = TestRequest::get().uri(&format!("/users?email={}&page=2", domain)).send_request(&mut app).await;
+        let found: Vec<User> = test::read_body_json(resp).await;
+        assert_eq!(found.len(), 1);
+
+        // An underscore would match any character if it wasn't escaped
+        let resp = TestRequest::get().uri(&format!("/users?email={}", domain.replace('-', "_"))).send_request(&mut app).await;
+        let found: Vec<User> = test::read_body_json(resp).await;
+        assert!(found.is_empty(), "Wildcards in the email filter should be matched literally");
+
+        let resp = TestRequest::get().uri("/users?email=%25").send_request(&mut app).await;
+        let found: Vec<User> = test::read_body_json(resp).await;
+        assert!(found.is_empty(), "A percent sign should not match every user");
+
+        for user in users {
+            User::delete(user.id).unwrap();
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_list_is_never_stale_after_write() {
+        crate::test::init();
+
+        let email = format!("{}@cloudmaker.dev", Uuid::new_v4());
+        let list_uri = format!("/users?email={}", email);
+
+        let mut app = test::init_service(App::new().configure(init_routes)).await;
+
+        // Warm the cache with an empty list
+        let resp = TestRequest::get().uri(&list_uri).send_request(&mut app).await;
+        let users: Vec<User> = test::read_body_json(resp).await;
+        assert!(users.is_empty());
+
+        let request_body = json!({ "email": email, "password": "test" });
+        let resp = TestRequest::post().uri("/users").set_json(&request_body).send_request(&mut app).await;
+        assert!(resp.status().is_success(), "Failed to create user");
+        let user: User = test::read_body_json(resp).await;
+
+        let resp = TestRequest::get().uri(&list_uri).send_request(&mut app).await;
+        let users: Vec<User> = test::read_body_json(resp).await;
+        assert_eq!(users.len(), 1, "Created user missing from list");
+
+        let request_body = json!({ "email": email, "password": "new" });
+        let resp = TestRequest::put().uri(&format!("/users/{}", user.id)).set_json(&request_body).send_request(&mut app).await;
+        assert!(resp.status().is_success(), "Failed to update user");
+
+        let resp = TestRequest::get().uri(&list_uri).send_request(&mut app).await;
+        let users: Vec<User> = test::read_body_json(resp).await;
+        assert_eq!(users[0].password, "new", "List served a stale user after update");
+
+        let resp = TestRequest::delete().uri(&format!("/users/{}", user.id)).send_request(&mut app).await;
+        assert!(resp.status().is_success(), "Failed to delete user");
+
+        let resp = TestRequest::get().uri(&list_uri).send_request(&mut app).await;
+        let users: Vec<User> = test::read_body_json(resp).await;
+        assert!(users.is_empty(), "List served a deleted user");
+    }
+
+    #[test]
+    fn test_concurrent_misses_load_once() {
+        crate::test::init();
+
+        let id = Uuid::new_v4();
+        let loads = Arc::new(AtomicUsize::new(0));
+
+        let handles: Vec<_> = (0..8).map(|_| {
+            let loads = loads.clone();
+            thread::spawn(move || {
+                User::cached(&id, || {
+                    loads.fetch_add(1, Ordering::SeqCst);
+                    thread::sleep(Duration::from_millis(100));
+                    Err(ApiError::new(404, "Record not found".to_string()))
+                })
+            })
+        }).collect();
+
+        for handle in handles {
+            let res = handle.join().unwrap();
+            assert_eq!(res.err().map(|e| e.status_code), Some(404));
+        }
+
+        assert_eq!(loads.load(Ordering::SeqCst), 1, "Concurrent misses should only load once");
+
+        // The 404 is cached, so the next lookup doesn't load either
+        let res = User::cached(&id, || {
+            loads.fetch_add(1, Ordering::SeqCst);
+            Err(ApiError::new(404, "Record not found".to_string()))
+        });
+        assert_eq!(res.err().map(|e| e.status_code), Some(404));
+        assert_eq!(loads.load(Ordering::SeqCst), 1, "Not found should be cached");
+    }
+
+    #[test]
+    fn test_find_is_served_from_local_tier() {
+        crate::test::init();
+
+        let email = format!("{}@cloudmaker.dev", Uuid::new_v4());
+        let user = User::create(UserMessage { email, password: "test".to_string() }).unwrap();
+
+        let before = cache::metrics().local.hits;
+        let found = User::find(user.id).unwrap();
+        assert_eq!(found.id, user.id);
+        assert!(cache::metrics().local.hits > before, "Expected a local cache hit");
+
+        User::delete(user.id).unwrap();
+        assert!(User::cache_find(&user.id).is_none(), "Deleted user should be gone from both tiers");
+    }
+
+    #[test]
+    fn test_only_writes_publish_invalidations() {
+        crate::test::init();
+
+        let client = redis::Client::open(env::var("REDIS_URL").unwrap()).unwrap();
+        let mut conn = client.get_connection().unwrap();
+        let mut pubsub = conn.as_pubsub();
+        pubsub.subscribe(cache::INVALIDATION_CHANNEL).unwrap();
+        pubsub.set_read_timeout(Some(Duration::from_millis(500))).unwrap();
+
+        let email = format!("{}@cloudmaker.dev", Uuid::new_v4());
+        let user = User::create(UserMessage { email, password: "test".to_string() }).unwrap();
+        let key = User::cache_config().key(user.id);
+
+        let mut published = |key: &str| {
+            while let Ok(message) = pubsub.get_message() {
+                let payload: String = message.get_payload().unwrap();
+                if payload.ends_with(&format!(" {}", key)) {
+                    return true;
+                }
+            }
+            false
+        };
+        assert!(published(&key), "Creating a user should publish an invalidation");
+
+        // A miss that fills the cache from the database isn't a change
+        let missing = Uuid::new_v4();
+        let res = User::find(missing);
+        assert_eq!(res.err().map(|e| e.status_code), Some(404));
+        assert!(!published(&User::cache_config().key(missing)), "A read should not publish an invalidation");
+
+        User::delete(user.id).unwrap();
+        assert!(published(&key), "Deleting a user should publish an invalidation");
+    }
+
+    #[test]
+    fn test_no_stale_reads_after_outage() {
+        crate::test::init();
+
+        let email = format!("{}@cloudmaker.dev", Uuid::new_v4());
+        let params = || Params { email: Some(email.clone()), ..Params::default() };
+        let created = User::create(UserMessage { email: email.clone(), password: "old".to_string() }).unwrap();
+
+        // Warm the cache
+        User::find(created.id).unwrap();
+        User::find_all(params()).unwrap();
+
+        // An update made while redis was unreachable, which leaves the old user and list in redis
+        let conn = db::connection().unwrap();
+        diesel::update(user::table.filter(user::id.eq(created.id)))
+            .set(user::password.eq("new"))
+            .execute(&conn)
+            .unwrap();
+        let key = User::cache_config().key(created.id);
+        cache::defer(Invalidation::Delete(key.clone()));
+        cache::invalidate(&key);
+        User::cache_invalidate_lists();
+
+        // The first reads after redis is back must not see the old entries
+        assert_eq!(User::find(created.id).unwrap().password, "new", "Find served a stale user after the outage");
+        assert_eq!(User::find_all(params()).unwrap()[0].password, "new", "List served a stale user after the outage");
+
+        crate::test::wait_for_cache_replay();
+
+        assert_eq!(User::find(created.id).unwrap().password, "new");
+        assert_eq!(User::find_all(params()).unwrap()[0].password, "new");
+
+        User::delete(created.id).unwrap();
+    }
+}
@@ -0,0 +1,4 @@
+
+DROP TABLE password_reset_token;
+
+ALTER TABLE "user" DROP COLUMN session_version;
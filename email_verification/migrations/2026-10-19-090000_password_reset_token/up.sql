@@ -0,0 +1,9 @@
+
+ALTER TABLE "user" ADD COLUMN session_version INTEGER NOT NULL DEFAULT 0;
+
+CREATE TABLE password_reset_token (
+    id BYTEA PRIMARY KEY,
+    user_id UUID UNIQUE NOT NULL REFERENCES "user" (id) ON DELETE CASCADE,
+    expires_at TIMESTAMP NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT current_timestamp
+);
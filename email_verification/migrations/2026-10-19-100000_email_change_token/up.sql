@@ -0,0 +1,9 @@
+
+-- Pending email changes are kept apart from invitations, so one can't overwrite the other
+CREATE TABLE email_change_token (
+    id BYTEA PRIMARY KEY,
+    user_id UUID UNIQUE NOT NULL REFERENCES "user" (id) ON DELETE CASCADE,
+    email TEXT NOT NULL,
+    expires_at TIMESTAMP NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT current_timestamp
+);
@@ -0,0 +1,10 @@
+
+ALTER TABLE "user" ADD COLUMN totp_secret BYTEA;
+ALTER TABLE "user" ADD COLUMN totp_enabled BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE "user" ADD COLUMN totp_last_step BIGINT;
+
+CREATE TABLE recovery_code (
+    id BYTEA PRIMARY KEY,
+    user_id UUID NOT NULL REFERENCES "user" (id) ON DELETE CASCADE,
+    created_at TIMESTAMP NOT NULL DEFAULT current_timestamp
+);
@@ -0,0 +1,2 @@
+
+DROP TABLE outbound_email;
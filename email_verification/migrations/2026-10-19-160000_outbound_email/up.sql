@@ -0,0 +1,14 @@
+
+CREATE TABLE outbound_email (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    payload TEXT NOT NULL,
+    status TEXT NOT NULL DEFAULT 'pending',
+    attempts INTEGER NOT NULL DEFAULT 0,
+    next_attempt_at TIMESTAMP NOT NULL DEFAULT current_timestamp,
+    last_error TEXT,
+    message_id TEXT,
+    sent_at TIMESTAMP,
+    created_at TIMESTAMP NOT NULL DEFAULT current_timestamp
+);
+
+CREATE INDEX outbound_email_due_idx ON outbound_email (status, next_attempt_at);
@@ -0,0 +1,3 @@
+
+DROP TABLE email_suppression;
+DROP TABLE email_event;
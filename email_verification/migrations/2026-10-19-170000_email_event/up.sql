@@ -0,0 +1,18 @@
+
+CREATE TABLE email_event (
+    id UUID PRIMARY KEY DEFAULT uuid_generate_v4(),
+    message_id TEXT NOT NULL,
+    email TEXT NOT NULL,
+    event TEXT NOT NULL,
+    reason TEXT,
+    occurred_at TIMESTAMP NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT current_timestamp,
+    UNIQUE (message_id, event, occurred_at)
+);
+
+CREATE TABLE email_suppression (
+    email TEXT PRIMARY KEY,
+    reason TEXT NOT NULL,
+    message_id TEXT,
+    created_at TIMESTAMP NOT NULL DEFAULT current_timestamp
+);
@@ -0,0 +1,7 @@
+
+-- The raw tokens can't be recovered from their hashes
+DELETE FROM email_verification_token;
+ALTER TABLE email_verification_token DROP COLUMN salt;
+
+DELETE FROM email_change_token;
+ALTER TABLE email_change_token DROP COLUMN salt;
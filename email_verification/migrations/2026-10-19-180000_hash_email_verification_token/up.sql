@@ -0,0 +1,9 @@
+
+-- Tokens are looked up by their SHA-256 hash, so hash the ones that are still out there.
+-- Without a salt their secret can't be derived again, so they can be redeemed but not sent.
+UPDATE email_verification_token SET id = sha256(id);
+ALTER TABLE email_verification_token ADD COLUMN salt BYTEA;
+
+-- Pending email changes are short lived, so they are requested again instead
+DELETE FROM email_change_token;
+ALTER TABLE email_change_token ADD COLUMN salt BYTEA NOT NULL;
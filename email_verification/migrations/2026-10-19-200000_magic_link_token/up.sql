@@ -0,0 +1,7 @@
+
+CREATE TABLE magic_link_token (
+    id BYTEA PRIMARY KEY,
+    user_id UUID UNIQUE NOT NULL REFERENCES "user" (id) ON DELETE CASCADE,
+    expires_at TIMESTAMP NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT current_timestamp
+);
@@ -0,0 +1,109 @@
+use crate::active_session::ActiveSession;
+use crate::api_error::ApiError;
+use crate::auth;
+use crate::email::Mailer;
+use crate::email_change_token::EmailChangeToken;
+use crate::db;
+use crate::outbound_email::OutboundMessage;
+use crate::user::User;
+use actix_web::{post, web, HttpRequest, HttpResponse};
+use actix_session::Session;
+use diesel::Connection;
+use hex;
+use serde::Deserialize;
+use serde_json::json;
+
+#[derive(Deserialize)]
+struct ChangePasswordMessage {
+    current_password: String,
+    new_password: String,
+}
+
+#[post("/me/password")]
+async fn change_password(body: web::Json<ChangePasswordMessage>, session: Session, req: HttpRequest) -> Result<HttpResponse, ApiError> {
+    let body = body.into_inner();
+    let user = auth::current_user(&session)?;
+
+    if !user.verify_password(body.current_password.as_bytes())? {
+        return Err(ApiError::new(403, "Current password is not valid"));
+    }
+
+    let conn = db::connection()?;
+    let user = User::set_password(&conn, user.id, body.new_password)?;
+
+    // Changing the password signs out every session, so start a new one for this client
+    ActiveSession::delete_all(user.id)?;
+    auth::sign_in(&session, &user, &req)?;
+
+    Ok(HttpResponse::Ok().json(json!({"message": "Password successfully changed"})))
+}
+
+#[derive(Deserialize)]
+struct ChangeEmailMessage {
+    email: String,
+}
+
+#[post("/me/email")]
+async fn change_email(body: web::Json<ChangeEmailMessage>, session: Session, mailer: web::Data<Mailer>, req: HttpRequest) -> Result<HttpResponse, ApiError> {
+    let body = body.into_inner();
+    let user = auth::current_user(&session)?;
+    let locale = mailer.locale(&req);
+
+    match User::find_by_email(body.email.clone()) {
+        Ok(_) => return Err(ApiError::new(409, "Email is already in use")),
+        Err(e) if e.status_code == 404 => (),
+        Err(e) => return Err(e),
+    }
+
+    let conn = db::connection()?;
+
+    conn.transaction(|| {
+        EmailChangeToken::create(&conn, user.id, body.email)?;
+        OutboundMessage::ChangeEmail { user_id: user.id, locale }.queue(&conn)
+    })?;
+
+    Ok(HttpResponse::Ok().json(json!({"message": "Verification email sent"})))
+}
+
+#[derive(Deserialize)]
+struct ConfirmEmailMessage {
+    token: String,
+}
+
+#[post("/me/email/confirm")]
+async fn confirm_email(body: web::Json<ConfirmEmailMessage>, session: Session) -> Result<HttpResponse, ApiError> {
+    let body = body.into_inner();
+    let user = auth::current_user(&session)?;
+
+    let secret = hex::decode(body.token)
+        .map_err(|_| ApiError::new(403, "Invalid token"))?;
+
+    let conn = db::connection()?;
+
+    let user = conn.transaction(|| {
+        let token = EmailChangeToken::redeem(&conn, &secret, user.id)
+            .map_err(|e| {
+                match e.status_code {
+                    404 => ApiError::new(403, "Invalid token"),
+                    _ => e,
+                }
+            })?;
+
+        // Someone else may have taken the address since the change was requested
+        User::set_email(&conn, user.id, token.email)
+            .map_err(|e| {
+                match e.status_code {
+                    409 => ApiError::new(409, "Email is already in use"),
+                    _ => e,
+                }
+            })
+    })?;
+
+    Ok(HttpResponse::Ok().json(json!({"message": "Email successfully changed", "user": user})))
+}
+
+pub fn init_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(change_password);
+    cfg.service(change_email);
+    cfg.service(confirm_email);
+}
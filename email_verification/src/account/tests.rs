@@ -0,0 +1,85 @@
+
+#[cfg(test)]
+mod tests {
+    use crate::account::init_routes;
+    use crate::auth;
+    use crate::db;
+    use crate::email_change_token::EmailChangeToken;
+    use crate::email_verification_token::{EmailVerificationToken, EmailVerificationTokenMessage};
+    use crate::user::{User, UserMessage};
+    use actix_redis::RedisSession;
+    use actix_web::{test::{self, TestRequest}, App};
+    use serde_json::{json, Value};
+    use std::env;
+    use uuid::Uuid;
+
+    fn create_user() -> User {
+        let email = format!("{}@cloudmaker.dev", Uuid::new_v4());
+        User::create(&db::connection().unwrap(), UserMessage { email, password: "test".to_string() })
+            .expect("Failed to create user")
+    }
+
+    #[actix_rt::test]
+    async fn test_change_email_does_not_clash_with_invitations() {
+        let mailer = crate::test::mailer();
+        let user = create_user();
+        let other = create_user();
+        let invited = format!("{}@cloudmaker.dev", Uuid::new_v4());
+        let conn = db::connection().unwrap();
+
+        let redis_port = env::var("REDIS_PORT").expect("Redis port not set");
+        let redis_host = env::var("REDIS_HOST").expect("Redis host not set");
+
+        let mut app = test::init_service(
+            App::new()
+                .app_data(mailer.clone())
+                .wrap(RedisSession::new(format!("{}:{}", redis_host, redis_port), &[0; 32]))
+                .configure(auth::init_routes)
+                .configure(init_routes)
+        ).await;
+
+        let req = TestRequest::post().uri("/sign-in").set_json(&json!({ "email": user.email, "password": "test" })).to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert!(resp.status().is_success(), "Failed to sign in");
+        let cookie = resp.response().cookies().next().expect("No session cookie").into_owned();
+
+        // A pending invitation survives a change to the same address
+        let (_, invite_secret) = EmailVerificationToken::create(&conn, EmailVerificationTokenMessage { id: None, email: invited.clone() })
+            .expect("Failed to create invitation");
+
+        let req = TestRequest::post().uri("/me/email").cookie(cookie.clone()).set_json(&json!({ "email": invited })).to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert!(resp.status().is_success(), "Failed to request an email change");
+
+        EmailVerificationToken::redeem(&conn, &invite_secret, &invited)
+            .expect("Email change should not overwrite the invitation");
+
+        // A pending change survives a later invitation to the same address
+        let (_, change_secret) = EmailChangeToken::create(&conn, user.id, invited.clone()).unwrap();
+        let (invitation, _) = EmailVerificationToken::create(&conn, EmailVerificationTokenMessage { id: None, email: invited.clone() }).unwrap();
+
+        let req = TestRequest::post().uri("/me/email/confirm").cookie(cookie.clone()).set_json(&json!({ "token": hex::encode(&change_secret) })).to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert!(resp.status().is_success(), "Invitation should not overwrite the email change");
+        let body: Value = test::read_body_json(resp).await;
+        assert_eq!(json!(invited), body["user"]["email"]);
+
+        let req = TestRequest::post().uri("/me/email").cookie(cookie.clone()).set_json(&json!({ "email": other.email })).to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(409, resp.status().as_u16(), "Changing to an address in use should conflict");
+        let body: Value = test::read_body_json(resp).await;
+        assert_eq!(json!({ "message": "Email is already in use" }), body);
+
+        // The address is taken between the request and the confirmation
+        let (_, change_secret) = EmailChangeToken::create(&conn, user.id, other.email.clone()).unwrap();
+        let req = TestRequest::post().uri("/me/email/confirm").cookie(cookie).set_json(&json!({ "token": hex::encode(&change_secret) })).to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(409, resp.status().as_u16());
+        let body: Value = test::read_body_json(resp).await;
+        assert_eq!(json!({ "message": "Email is already in use" }), body, "The database error should not leak");
+
+        EmailVerificationToken::delete(&invitation.id).unwrap();
+        User::delete(user.id).unwrap();
+        User::delete(other.id).unwrap();
+    }
+}
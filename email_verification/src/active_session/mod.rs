@@ -0,0 +1,6 @@
+mod model;
+mod routes;
+mod tests;
+
+pub use model::ActiveSession;
+pub use routes::init_routes;
@@ -0,0 +1,130 @@
+use crate::api_error::ApiError;
+use crate::cache;
+use actix_web::HttpRequest;
+use chrono::{NaiveDateTime, Utc};
+use redis::Commands;
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+use uuid::Uuid;
+
+// Matches the lifetime of the redis session cookie
+const SESSION_TTL: usize = 7 * 24 * 60 * 60;
+
+#[derive(Serialize, Deserialize)]
+pub struct ActiveSession {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub last_seen_at: NaiveDateTime,
+}
+
+impl ActiveSession {
+    pub fn find(id: Uuid) -> Result<Option<Self>, ApiError> {
+        let mut cache = cache::connection()?;
+        let res: Option<Vec<u8>> = cache.get(session_key(id))?;
+        Ok(res.and_then(|res| serde_json::from_slice(&res).ok()))
+    }
+
+    pub fn find_all(user_id: Uuid) -> Result<Vec<Self>, ApiError> {
+        let mut cache = cache::connection()?;
+        let ids: Vec<String> = cache.smembers(user_sessions_key(user_id))?;
+
+        let mut sessions = Vec::new();
+        for id in ids {
+            let session = match Uuid::parse_str(&id) {
+                Ok(id) => ActiveSession::find(id)?,
+                Err(_) => None,
+            };
+
+            match session {
+                Some(session) => sessions.push(session),
+                // The session has expired, so it no longer belongs in the index
+                None => {
+                    let _: () = cache.srem(user_sessions_key(user_id), &id)?;
+                },
+            }
+        }
+
+        sessions.sort_by(|a, b| b.last_seen_at.cmp(&a.last_seen_at));
+
+        Ok(sessions)
+    }
+
+    pub fn create(user_id: Uuid, req: &HttpRequest) -> Result<Self, ApiError> {
+        let now = Utc::now().naive_utc();
+        let user_agent = req.headers()
+            .get("user-agent")
+            .and_then(|user_agent| user_agent.to_str().ok())
+            .map(|user_agent| user_agent.to_string());
+
+        let session = ActiveSession {
+            id: Uuid::new_v4(),
+            user_id,
+            ip: req.connection_info().remote().map(|ip| ip.to_string()),
+            user_agent,
+            created_at: now,
+            last_seen_at: now,
+        };
+
+        session.save()?;
+
+        let mut cache = cache::connection()?;
+        let _: () = cache.sadd(user_sessions_key(user_id), session.id.to_string())?;
+        let _: () = cache.expire(user_sessions_key(user_id), SESSION_TTL)?;
+
+        Ok(session)
+    }
+
+    pub fn touch(mut self) -> Result<Self, ApiError> {
+        self.last_seen_at = Utc::now().naive_utc();
+        self.save()?;
+
+        let mut cache = cache::connection()?;
+        let _: () = cache.expire(user_sessions_key(self.user_id), SESSION_TTL)?;
+
+        Ok(self)
+    }
+
+    pub fn delete(user_id: Uuid, id: Uuid) -> Result<usize, ApiError> {
+        let mut cache = cache::connection()?;
+        let removed: usize = cache.srem(user_sessions_key(user_id), id.to_string())?;
+
+        // Only sessions in the user's own index are deleted, so one user can't revoke another's
+        if removed == 1 {
+            let _: () = cache.del(session_key(id))?;
+        }
+
+        Ok(removed)
+    }
+
+    pub fn delete_all(user_id: Uuid) -> Result<usize, ApiError> {
+        let mut cache = cache::connection()?;
+        let ids: Vec<String> = cache.smembers(user_sessions_key(user_id))?;
+
+        for id in &ids {
+            let _: () = cache.del(session_key(id))?;
+        }
+
+        let _: () = cache.del(user_sessions_key(user_id))?;
+
+        Ok(ids.len())
+    }
+
+    fn save(&self) -> Result<(), ApiError> {
+        let mut cache = cache::connection()?;
+        let value = serde_json::to_vec(self)
+            .map_err(|e| ApiError::new(500, format!("Failed to serialize session: {}", e)))?;
+        let _: () = cache.set_ex(session_key(self.id), value, SESSION_TTL)?;
+        Ok(())
+    }
+}
+
+fn session_key<T: Display>(id: T) -> String {
+    format!("session.{}", id)
+}
+
+fn user_sessions_key(user_id: Uuid) -> String {
+    format!("user.{}.sessions", user_id)
+}
@@ -0,0 +1,74 @@
+use crate::active_session::ActiveSession;
+use crate::api_error::ApiError;
+use crate::auth::{self, Authenticated};
+use actix_web::{delete, get, web, HttpResponse};
+use actix_session::Session;
+use serde_json::json;
+use uuid::Uuid;
+
+#[get("/me/sessions")]
+async fn find_all(session: Session) -> Result<HttpResponse, ApiError> {
+    let user = auth::current_user(&session)?;
+    let current_id: Option<Uuid> = session.get("session_id")?;
+
+    let sessions: Vec<_> = ActiveSession::find_all(user.id)?
+        .into_iter()
+        .map(|active_session| {
+            let current = Some(active_session.id) == current_id;
+            json!({
+                "id": active_session.id,
+                "ip": active_session.ip,
+                "user_agent": active_session.user_agent,
+                "created_at": active_session.created_at,
+                "last_seen_at": active_session.last_seen_at,
+                "current": current,
+            })
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok().json(sessions))
+}
+
+#[delete("/me/sessions/{id}")]
+async fn delete(id: web::Path<Uuid>, session: Session) -> Result<HttpResponse, ApiError> {
+    let user = auth::current_user(&session)?;
+    let id = id.into_inner();
+
+    let num_deleted = ActiveSession::delete(user.id, id)?;
+    if num_deleted == 0 {
+        return Err(ApiError::new(404, "Session not found"));
+    }
+
+    let current_id: Option<Uuid> = session.get("session_id")?;
+    if current_id == Some(id) {
+        session.purge();
+    }
+
+    Ok(HttpResponse::Ok().json(json!({ "deleted": num_deleted })))
+}
+
+#[delete("/me/sessions")]
+async fn delete_all(session: Session) -> Result<HttpResponse, ApiError> {
+    let user = auth::current_user(&session)?;
+
+    let num_deleted = ActiveSession::delete_all(user.id)?;
+    session.purge();
+
+    Ok(HttpResponse::Ok().json(json!({ "deleted": num_deleted })))
+}
+
+#[delete("/users/{id}/sessions")]
+async fn admin_delete_all(id: web::Path<Uuid>, auth: Authenticated) -> Result<HttpResponse, ApiError> {
+    auth.require_admin()?;
+
+    let num_deleted = ActiveSession::delete_all(id.into_inner())?;
+
+    Ok(HttpResponse::Ok().json(json!({ "deleted": num_deleted })))
+}
+
+pub fn init_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(find_all);
+    cfg.service(delete);
+    cfg.service(delete_all);
+    cfg.service(admin_delete_all);
+}
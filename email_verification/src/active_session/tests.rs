@@ -0,0 +1,66 @@
+
+#[cfg(test)]
+mod tests {
+    use crate::active_session::init_routes;
+    use crate::auth;
+    use crate::db;
+    use crate::user::{User, UserMessage};
+    use actix_redis::RedisSession;
+    use actix_web::{test::{self, TestRequest}, App};
+    use serde_json::{json, Value};
+    use std::env;
+    use uuid::Uuid;
+
+    #[actix_rt::test]
+    async fn test_cannot_revoke_another_users_session() {
+        crate::test::init();
+
+        let mut users = Vec::new();
+        for _ in 0..2 {
+            let email = format!("{}@cloudmaker.dev", Uuid::new_v4());
+            users.push(User::create(&db::connection().unwrap(), UserMessage { email, password: "test".to_string() }).unwrap());
+        }
+
+        let redis_port = env::var("REDIS_PORT").expect("Redis port not set");
+        let redis_host = env::var("REDIS_HOST").expect("Redis host not set");
+
+        let mut app = test::init_service(
+            App::new()
+                .wrap(RedisSession::new(format!("{}:{}", redis_host, redis_port), &[0; 32]))
+                .configure(auth::init_routes)
+                .configure(init_routes)
+        ).await;
+
+        let mut cookies = Vec::new();
+        for user in &users {
+            let req = TestRequest::post().uri("/sign-in").set_json(&json!({ "email": user.email, "password": "test" })).to_request();
+            let resp = test::call_service(&mut app, req).await;
+            cookies.push(resp.response().cookies().next().expect("No session cookie").into_owned());
+        }
+
+        let req = TestRequest::get().uri("/me/sessions").cookie(cookies[1].clone()).to_request();
+        let resp = test::call_service(&mut app, req).await;
+        let sessions: Value = test::read_body_json(resp).await;
+        let other_session_id = sessions[0]["id"].as_str().expect("No session found").to_string();
+
+        let req = TestRequest::delete().uri(&format!("/me/sessions/{}", other_session_id)).cookie(cookies[0].clone()).to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(404, resp.status().as_u16(), "Another user's session should not be found");
+
+        let req = TestRequest::get().uri("/who-am-i").cookie(cookies[1].clone()).to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert!(resp.status().is_success(), "The other user's session should still be active");
+
+        let req = TestRequest::delete().uri(&format!("/me/sessions/{}", other_session_id)).cookie(cookies[1].clone()).to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert!(resp.status().is_success(), "Failed to revoke own session");
+
+        let req = TestRequest::get().uri("/who-am-i").cookie(cookies[1].clone()).to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(401, resp.status().as_u16(), "A revoked session should no longer work");
+
+        for user in users {
+            User::delete(user.id).unwrap();
+        }
+    }
+}
@@ -1,7 +1,8 @@
 use actix_web::http::StatusCode;
 use actix_web::{HttpResponse, ResponseError};
-use actix_web::error::Error as ActixError;
+use actix_web::error::{BlockingError, Error as ActixError};
 use diesel::result::Error as DieselError;
+use redis::RedisError;
 use serde::Deserialize;
 use serde_json::json;
 use std::fmt;
@@ -34,12 +35,27 @@ impl From<DieselError> for ApiError {
     }
 }
 
+impl From<RedisError> for ApiError {
+    fn from(error: RedisError) -> ApiError {
+        ApiError::new(500, format!("Redis error: {}", error))
+    }
+}
+
 impl From<ActixError> for ApiError {
     fn from(error: ActixError) -> ApiError {
         ApiError::new(500, error.to_string())
     }
 }
 
+impl From<BlockingError<ApiError>> for ApiError {
+    fn from(error: BlockingError<ApiError>) -> ApiError {
+        match error {
+            BlockingError::Error(error) => error,
+            BlockingError::Canceled => ApiError::new(500, "Blocking operation was canceled"),
+        }
+    }
+}
+
 impl ResponseError for ApiError {
     fn error_response(&self) -> HttpResponse {
         let status_code = match StatusCode::from_u16(self.status_code) {
@@ -0,0 +1,112 @@
+use crate::api_error::ApiError;
+use crate::db;
+use crate::schema::api_key;
+use chrono::{NaiveDateTime, Utc};
+use diesel::prelude::*;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+pub const SCOPES: &[&str] = &["users:read", "users:write"];
+
+#[derive(Deserialize)]
+pub struct ApiKeyMessage {
+    pub name: String,
+    pub scopes: Vec<String>,
+    pub expires_at: Option<NaiveDateTime>,
+}
+
+#[derive(Serialize, Deserialize, Queryable, Insertable)]
+#[table_name = "api_key"]
+pub struct ApiKey {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub name: String,
+    #[serde(skip_serializing)]
+    pub key_hash: Vec<u8>,
+    pub scopes: Vec<String>,
+    pub expires_at: Option<NaiveDateTime>,
+    pub last_used_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+}
+
+impl ApiKey {
+    pub fn find_all(user_id: Uuid) -> Result<Vec<Self>, ApiError> {
+        let conn = db::connection()?;
+
+        let api_keys = api_key::table
+            .filter(api_key::user_id.eq(user_id))
+            .order(api_key::created_at.desc())
+            .load::<ApiKey>(&conn)?;
+
+        Ok(api_keys)
+    }
+
+    // Only a hash of the secret is stored, so the returned secret can't be shown again later
+    pub fn create(user_id: Uuid, body: ApiKeyMessage) -> Result<(Self, String), ApiError> {
+        if let Some(scope) = body.scopes.iter().find(|scope| !SCOPES.contains(&scope.as_str())) {
+            return Err(ApiError::new(400, format!("Unknown scope: {}", scope)));
+        }
+
+        let conn = db::connection()?;
+
+        let secret = hex::encode(rand::thread_rng().gen::<[u8; 32]>());
+        let api_key = ApiKey {
+            id: Uuid::new_v4(),
+            user_id,
+            name: body.name,
+            key_hash: hash(&secret),
+            scopes: body.scopes,
+            expires_at: body.expires_at,
+            last_used_at: None,
+            created_at: Utc::now().naive_utc(),
+        };
+
+        let api_key = diesel::insert_into(api_key::table)
+            .values(api_key)
+            .get_result(&conn)?;
+
+        Ok((api_key, secret))
+    }
+
+    pub fn authenticate(secret: &str) -> Result<Self, ApiError> {
+        let conn = db::connection()?;
+        let now = Utc::now().naive_utc();
+
+        let api_key = diesel::update(api_key::table)
+            .filter(api_key::key_hash.eq(hash(secret)))
+            .filter(api_key::expires_at.is_null().or(api_key::expires_at.gt(now)))
+            .set(api_key::last_used_at.eq(now))
+            .get_result(&conn)
+            .map_err(|e| {
+                match e {
+                    diesel::result::Error::NotFound => ApiError::new(401, "Invalid api key"),
+                    e => ApiError::from(e),
+                }
+            })?;
+
+        Ok(api_key)
+    }
+
+    pub fn delete(user_id: Uuid, id: Uuid) -> Result<usize, ApiError> {
+        let conn = db::connection()?;
+
+        let res = diesel::delete(
+                api_key::table
+                    .filter(api_key::id.eq(id))
+                    .filter(api_key::user_id.eq(user_id))
+            )
+            .execute(&conn)?;
+
+        Ok(res)
+    }
+
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scopes.iter().any(|s| s == scope)
+    }
+}
+
+fn hash(secret: &str) -> Vec<u8> {
+    Sha256::digest(secret.trim().as_bytes()).to_vec()
+}
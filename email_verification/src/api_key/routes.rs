@@ -0,0 +1,34 @@
+use crate::api_error::ApiError;
+use crate::api_key::{ApiKey, ApiKeyMessage};
+use crate::auth;
+use actix_web::{delete, get, post, web, HttpResponse};
+use actix_session::Session;
+use serde_json::json;
+use uuid::Uuid;
+
+#[get("/me/api-keys")]
+async fn find_all(session: Session) -> Result<HttpResponse, ApiError> {
+    let user = auth::current_user(&session)?;
+    let api_keys = ApiKey::find_all(user.id)?;
+    Ok(HttpResponse::Ok().json(api_keys))
+}
+
+#[post("/me/api-keys")]
+async fn create(body: web::Json<ApiKeyMessage>, session: Session) -> Result<HttpResponse, ApiError> {
+    let user = auth::current_user(&session)?;
+    let (api_key, secret) = ApiKey::create(user.id, body.into_inner())?;
+    Ok(HttpResponse::Ok().json(json!({ "api_key": api_key, "secret": secret })))
+}
+
+#[delete("/me/api-keys/{id}")]
+async fn delete(id: web::Path<Uuid>, session: Session) -> Result<HttpResponse, ApiError> {
+    let user = auth::current_user(&session)?;
+    let num_deleted = ApiKey::delete(user.id, id.into_inner())?;
+    Ok(HttpResponse::Ok().json(json!({ "deleted": num_deleted })))
+}
+
+pub fn init_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(find_all);
+    cfg.service(create);
+    cfg.service(delete);
+}
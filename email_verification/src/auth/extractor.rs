@@ -0,0 +1,61 @@
+use crate::api_error::ApiError;
+use crate::api_key::ApiKey;
+use crate::auth::current_user;
+use crate::user::User;
+use actix_session::UserSession;
+use actix_web::dev::Payload;
+use actix_web::{FromRequest, HttpRequest};
+use futures::future::{ready, Ready};
+
+// The signed in user, authenticated either by an `Authorization: ApiKey <secret>`
+// header or by the session cookie.
+pub struct Authenticated {
+    pub user: User,
+    pub api_key: Option<ApiKey>,
+}
+
+impl Authenticated {
+    // Sessions are allowed to do anything the user can, api keys only what they were given scopes for
+    pub fn require_scope(&self, scope: &str) -> Result<(), ApiError> {
+        match &self.api_key {
+            Some(api_key) if !api_key.has_scope(scope) => {
+                Err(ApiError::new(403, format!("Api key is missing the {} scope", scope)))
+            },
+            _ => Ok(()),
+        }
+    }
+
+    pub fn require_admin(&self) -> Result<(), ApiError> {
+        match self.user.is_admin {
+            true => Ok(()),
+            false => Err(ApiError::new(403, "Forbidden")),
+        }
+    }
+
+    fn authenticate(req: &HttpRequest) -> Result<Self, ApiError> {
+        let authorization = req.headers()
+            .get("Authorization")
+            .and_then(|authorization| authorization.to_str().ok());
+
+        if let Some(authorization) = authorization {
+            if authorization.starts_with("ApiKey ") {
+                let api_key = ApiKey::authenticate(&authorization["ApiKey ".len()..])?;
+                let user = User::find(api_key.user_id)?;
+                return Ok(Authenticated { user, api_key: Some(api_key) });
+            }
+        }
+
+        let user = current_user(&req.get_session())?;
+        Ok(Authenticated { user, api_key: None })
+    }
+}
+
+impl FromRequest for Authenticated {
+    type Error = ApiError;
+    type Future = Ready<Result<Self, Self::Error>>;
+    type Config = ();
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        ready(Authenticated::authenticate(req))
+    }
+}
@@ -1,3 +1,8 @@
+mod extractor;
 mod routes;
+mod session;
+mod tests;
 
-pub use routes::init_routes;
+pub use extractor::Authenticated;
+pub use routes::{init_routes, sign_in_user};
+pub use session::{begin_two_factor, cancel_two_factor, current_user, record_two_factor_failure, sign_in, sign_out, two_factor_user};
@@ -1,26 +1,83 @@
 use crate::api_error::ApiError;
+use crate::db;
 use crate::user::{User, UserMessage};
-use crate::email::{Email, Contact};
+use crate::email::Mailer;
+use crate::email_suppression::EmailSuppression;
+use crate::email_throttle;
 use crate::email_verification_token::{EmailVerificationToken, EmailVerificationTokenMessage};
-use actix_web::{post, get, web, HttpResponse};
+use crate::magic_link_token::MagicLinkToken;
+use crate::outbound_email::OutboundMessage;
+use crate::password_reset_token::PasswordResetToken;
+use crate::active_session::ActiveSession;
+use super::{session, Authenticated};
+use actix_web::{post, get, web, HttpRequest, HttpResponse};
 use actix_session::Session;
-use chrono::Utc;
+use diesel::Connection;
 use hex;
 use serde::Deserialize;
 use serde_json::json;
 use uuid::Uuid;
 
 #[post("/invite")]
-async fn invite(body: web::Json<EmailVerificationTokenMessage>) -> Result<HttpResponse, ApiError> {
+async fn invite(body: web::Json<EmailVerificationTokenMessage>, mailer: web::Data<Mailer>, req: HttpRequest) -> Result<HttpResponse, ApiError> {
     let body = body.into_inner();
-    let token = EmailVerificationToken::create(body.clone())?;
-    let token_string = hex::encode(token.id);
 
-    Email::new(Contact::new("tore@cloudmaker.dev", "Cloudmaker"))
-        .add_recipient(body.email)
-        .set_subject("Confirm your email")
-        .set_html(format!("Your confirmation code is: {}", &token_string))
-        .send()?;
+    if let Some(retry_after) = email_throttle::check("invite", &body.email, &req)? {
+        return Ok(email_throttle::too_many_requests(retry_after));
+    }
+
+    // Answer as if the email was sent, so the response doesn't tell which addresses are suppressed
+    if EmailSuppression::is_suppressed(&body.email)? {
+        return Ok(HttpResponse::Ok().json(json!({"message": "Verification email sent"})));
+    }
+
+    let locale = mailer.locale(&req);
+    let conn = db::connection()?;
+
+    conn.transaction(|| {
+        EmailVerificationToken::create(&conn, body.clone())?;
+        OutboundMessage::Invite { email: body.email.clone(), locale }.queue(&conn)
+    })?;
+
+    Ok(HttpResponse::Ok().json(json!({"message": "Verification email sent"})))
+}
+
+#[derive(Deserialize)]
+struct ResendInviteMessage {
+    email: String,
+}
+
+// Sends the invitation again with the same token, so the link in the first email keeps working
+#[post("/invite/resend")]
+async fn resend_invite(body: web::Json<ResendInviteMessage>, mailer: web::Data<Mailer>, req: HttpRequest) -> Result<HttpResponse, ApiError> {
+    let body = body.into_inner();
+
+    if let Some(retry_after) = email_throttle::check("invite", &body.email, &req)? {
+        return Ok(email_throttle::too_many_requests(retry_after));
+    }
+
+    // Answer as if the email was sent, so the response doesn't tell which addresses are suppressed
+    if EmailSuppression::is_suppressed(&body.email)? {
+        return Ok(HttpResponse::Ok().json(json!({"message": "Verification email sent"})));
+    }
+
+    let locale = mailer.locale(&req);
+    let conn = db::connection()?;
+
+    conn.transaction(|| {
+        let can_resend = match EmailVerificationToken::find_unexpired(&conn, &body.email) {
+            Ok(token) => token.salt.is_some(),
+            Err(e) if e.status_code == 404 => false,
+            Err(e) => return Err(e),
+        };
+
+        // The invitation has expired, or is too old to be sent again
+        if !can_resend {
+            EmailVerificationToken::create(&conn, EmailVerificationTokenMessage { id: None, email: body.email.clone() })?;
+        }
+
+        OutboundMessage::Invite { email: body.email.clone(), locale }.queue(&conn)
+    })?;
 
     Ok(HttpResponse::Ok().json(json!({"message": "Verification email sent"})))
 }
@@ -35,53 +92,104 @@ struct RegistrationMessage {
 #[post("/register")]
 async fn register(body: web::Json<RegistrationMessage>) -> Result<HttpResponse, ApiError> {
     let body = body.into_inner();
-    let token_id = hex::decode(body.token)
+    let secret = hex::decode(body.token)
         .map_err(|_| ApiError::new(403, "Invalid token"))?;
-    
-    let token = EmailVerificationToken::find(&token_id)
-        .map_err(|e| {
-            match e.status_code {
-                404 => ApiError::new(403, "Invalid token"),
-                _ => e,
-            }
-        })?;
 
-    if token.email != body.email {
-        return Err(ApiError::new(403, "Invalid token"));
-    }
+    let conn = db::connection()?;
 
-    if token.expires_at < Utc::now().naive_utc() {
-        return Err(ApiError::new(403, "Token expired"));
-    }
- 
-    let user = User::create(UserMessage { email: body.email, password: body.password })?;
+    let user = conn.transaction(|| {
+        EmailVerificationToken::redeem(&conn, &secret, &body.email)
+            .map_err(|e| {
+                match e.status_code {
+                    404 => ApiError::new(403, "Invalid token"),
+                    _ => e,
+                }
+            })?;
+
+        User::create(&conn, UserMessage { email: body.email, password: body.password })
+    })?;
 
     Ok(HttpResponse::Ok().json(json!({"message": "Successfully registered", "user": user})))
 }
 
 #[post("/sign-in")]
-async fn sign_in(credentials: web::Json<UserMessage>, session: Session) -> Result<HttpResponse, ApiError> {
+async fn sign_in(credentials: web::Json<UserMessage>, session: Session, req: HttpRequest) -> Result<HttpResponse, ApiError> {
     let credentials = credentials.into_inner();
 
-    let user = User::find_by_email(credentials.email)
+    let user = match User::find_by_email(credentials.email) {
+        Ok(user) => Some(user),
+        Err(e) if e.status_code == 404 => None,
+        Err(e) => return Err(e),
+    };
+
+    let is_valid = match &user {
+        Some(user) => user.verify_password(credentials.password.as_bytes())?,
+        None => User::verify_dummy_password(credentials.password.as_bytes())?,
+    };
+
+    match user {
+        Some(mut user) if is_valid => {
+            if let Err(e) = user.rehash_password_if_outdated(&credentials.password) {
+                error!("Failed to rehash password: {}", e);
+            }
+
+            sign_in_user(user, &session, &req)
+        },
+        _ => Err(ApiError::new(401, "Credentials not valid!")),
+    }
+}
+
+// Starts the session for a user that has proven who they are, unless a
+// two-factor code is needed first.
+pub fn sign_in_user(user: User, session: &Session, req: &HttpRequest) -> Result<HttpResponse, ApiError> {
+    if user.totp_enabled {
+        session::begin_two_factor(session, &user)?;
+        return Ok(HttpResponse::Ok().json(json!({
+            "message": "Two-factor code required",
+            "two_factor_required": true,
+        })));
+    }
+
+    session::sign_in(session, &user, req)?;
+    Ok(HttpResponse::Ok().json(user))
+}
+
+#[derive(Deserialize)]
+struct MagicLinkRequestMessage {
+    email: String,
+}
+
+#[post("/sign-in/magic-link")]
+async fn request_magic_link(body: web::Json<MagicLinkRequestMessage>, mailer: web::Data<Mailer>, req: HttpRequest) -> Result<HttpResponse, ApiError> {
+    let body = body.into_inner();
+
+    if let Some(retry_after) = email_throttle::check("magic_link", &body.email, &req)? {
+        return Ok(email_throttle::too_many_requests(retry_after));
+    }
+
+    let locale = mailer.locale(&req);
+    let conn = db::connection()?;
+
+    // Like password resets, the account is looked up when the email is sent
+    OutboundMessage::MagicLink { email: body.email, locale }.queue(&conn)?;
+
+    Ok(HttpResponse::Ok().json(json!({"message": "If the email is registered, a sign in link has been sent"})))
+}
+
+#[get("/sign-in/magic-link/{token}")]
+async fn sign_in_with_magic_link(token: web::Path<String>, session: Session, req: HttpRequest) -> Result<HttpResponse, ApiError> {
+    let secret = hex::decode(token.into_inner())
+        .map_err(|_| ApiError::new(403, "Invalid token"))?;
+
+    let user = MagicLinkToken::redeem(&secret)
         .map_err(|e| {
             match e.status_code {
-                404 => ApiError::new(401, "Credentials not valid!"),
+                404 => ApiError::new(403, "Invalid token"),
                 _ => e,
             }
         })?;
-    
-    let is_valid = user.verify_password(credentials.password.as_bytes())?;
-
-    if is_valid == true {
-        session.set("user_id", user.id)?;
-        session.renew();
 
-        Ok(HttpResponse::Ok().json(user))
-    }
-    else {
-        Err(ApiError::new(401, "Credentials not valid!"))
-    }
+    sign_in_user(user, &session, &req)
 }
 
 #[post("/sign-out")]
@@ -89,7 +197,7 @@ async fn sign_out(session: Session) -> Result<HttpResponse, ApiError> {
     let id: Option<Uuid> = session.get("user_id")?;
 
     if let Some(_) = id {
-        session.purge();
+        session::sign_out(&session)?;
         Ok(HttpResponse::Ok().json(json!({ "message": "Successfully signed out" })))
     }
     else {
@@ -98,22 +206,64 @@ async fn sign_out(session: Session) -> Result<HttpResponse, ApiError> {
 }
 
 #[get("/who-am-i")]
-async fn who_am_i(session: Session) -> Result<HttpResponse, ApiError> {
-    let id: Option<Uuid> = session.get("user_id")?;
+async fn who_am_i(auth: Authenticated) -> Result<HttpResponse, ApiError> {
+    auth.require_scope("users:read")?;
+    Ok(HttpResponse::Ok().json(auth.user))
+}
 
-    if let Some(id) = id {
-        let user = User::find(id)?;
-        Ok(HttpResponse::Ok().json(user))
-    }
-    else {
-        Err(ApiError::new(401, "Unauthorized"))
-    }
+#[derive(Deserialize)]
+struct PasswordResetRequestMessage {
+    email: String,
+}
+
+#[post("/password-reset/request")]
+async fn request_password_reset(body: web::Json<PasswordResetRequestMessage>, mailer: web::Data<Mailer>, req: HttpRequest) -> Result<HttpResponse, ApiError> {
+    let body = body.into_inner();
+    let locale = mailer.locale(&req);
+
+    let conn = db::connection()?;
+
+    // The account is looked up when the email is sent, so the response can't be used to find out
+    // which emails have an account, neither by its content nor by how long it takes
+    OutboundMessage::PasswordReset { email: body.email, locale }.queue(&conn)?;
+
+    Ok(HttpResponse::Ok().json(json!({"message": "If the email is registered, a password reset email has been sent"})))
+}
+
+#[derive(Deserialize)]
+struct PasswordResetConfirmMessage {
+    token: String,
+    password: String,
+}
+
+#[post("/password-reset/confirm")]
+async fn confirm_password_reset(body: web::Json<PasswordResetConfirmMessage>) -> Result<HttpResponse, ApiError> {
+    let body = body.into_inner();
+    let secret = hex::decode(body.token)
+        .map_err(|_| ApiError::new(403, "Invalid token"))?;
+
+    let user = PasswordResetToken::redeem(&secret, body.password)
+        .map_err(|e| {
+            match e.status_code {
+                404 => ApiError::new(403, "Invalid token"),
+                _ => e,
+            }
+        })?;
+
+    ActiveSession::delete_all(user.id)?;
+
+    Ok(HttpResponse::Ok().json(json!({"message": "Password successfully reset"})))
 }
 
 pub fn init_routes(cfg: &mut web::ServiceConfig) {
     cfg.service(invite);
+    cfg.service(resend_invite);
     cfg.service(register);
     cfg.service(sign_in);
+    cfg.service(request_magic_link);
+    cfg.service(sign_in_with_magic_link);
     cfg.service(sign_out);
     cfg.service(who_am_i);
+    cfg.service(request_password_reset);
+    cfg.service(confirm_password_reset);
 }
@@ -0,0 +1,93 @@
+use crate::active_session::ActiveSession;
+use crate::api_error::ApiError;
+use crate::user::User;
+use actix_session::Session;
+use actix_web::HttpRequest;
+use chrono::{Duration, Utc};
+use uuid::Uuid;
+
+pub fn sign_in(session: &Session, user: &User, req: &HttpRequest) -> Result<(), ApiError> {
+    let active_session = ActiveSession::create(user.id, req)?;
+
+    cancel_two_factor(session);
+    session.remove("csrf_token");
+    session.set("user_id", user.id)?;
+    session.set("session_id", active_session.id)?;
+    session.set("session_version", user.session_version)?;
+    session.renew();
+
+    Ok(())
+}
+
+pub fn sign_out(session: &Session) -> Result<(), ApiError> {
+    let id: Option<Uuid> = session.get("user_id")?;
+    let session_id: Option<Uuid> = session.get("session_id")?;
+
+    if let (Some(id), Some(session_id)) = (id, session_id) {
+        ActiveSession::delete(id, session_id)?;
+    }
+
+    session.purge();
+
+    Ok(())
+}
+
+// The password has been verified, but the session is not signed in until a
+// valid two-factor code has been provided as well.
+pub fn begin_two_factor(session: &Session, user: &User) -> Result<(), ApiError> {
+    let expires_at = Utc::now() + Duration::minutes(5);
+
+    session.set("two_factor_user_id", user.id)?;
+    session.set("two_factor_expires_at", expires_at.timestamp())?;
+    session.remove("two_factor_failures");
+    session.renew();
+
+    Ok(())
+}
+
+// Counts the failed codes for the pending sign in, and returns the count
+pub fn record_two_factor_failure(session: &Session) -> Result<u32, ApiError> {
+    let failures = session.get::<u32>("two_factor_failures")?.unwrap_or(0) + 1;
+    session.set("two_factor_failures", failures)?;
+    Ok(failures)
+}
+
+// Drops the pending sign in, so the password has to be given again
+pub fn cancel_two_factor(session: &Session) {
+    session.remove("two_factor_user_id");
+    session.remove("two_factor_expires_at");
+    session.remove("two_factor_failures");
+}
+
+pub fn two_factor_user(session: &Session) -> Result<User, ApiError> {
+    let id: Option<Uuid> = session.get("two_factor_user_id")?;
+    let expires_at: Option<i64> = session.get("two_factor_expires_at")?;
+
+    match (id, expires_at) {
+        (Some(id), Some(expires_at)) if expires_at > Utc::now().timestamp() => User::find(id),
+        _ => Err(ApiError::new(401, "Unauthorized")),
+    }
+}
+
+// Sessions that have been revoked, or created before the user's session
+// version was bumped by e.g. a password reset, are no longer accepted.
+pub fn current_user(session: &Session) -> Result<User, ApiError> {
+    let id: Option<Uuid> = session.get("user_id")?;
+    let session_id: Option<Uuid> = session.get("session_id")?;
+    let version: Option<i32> = session.get("session_version")?;
+
+    if let (Some(id), Some(session_id), Some(version)) = (id, session_id, version) {
+        if let Some(active_session) = ActiveSession::find(session_id)? {
+            let user = User::find(id)?;
+
+            if active_session.user_id == user.id && user.session_version == version {
+                active_session.touch()?;
+                return Ok(user);
+            }
+        }
+
+        session.purge();
+    }
+
+    Err(ApiError::new(401, "Unauthorized"))
+}
@@ -0,0 +1,370 @@
+#[cfg(test)]
+mod tests {
+    use crate::auth::init_routes;
+    use crate::cache;
+    use crate::db;
+    use crate::email::MemoryTransport;
+    use crate::outbound_email;
+    use crate::email_verification_token::{EmailVerificationToken, EmailVerificationTokenMessage};
+    use crate::schema::email_verification_token;
+    use crate::user::{User, UserMessage};
+    use actix_redis::RedisSession;
+    use actix_web::{test::{self, TestRequest}, App};
+    use chrono::{Duration, Utc};
+    use diesel::prelude::*;
+    use redis::Commands;
+    use reqwest::Url;
+    use serde_json::{json, Value};
+    use std::env;
+    use uuid::Uuid;
+
+    #[actix_rt::test]
+    async fn test_sign_in_does_not_reveal_unknown_email() {
+        crate::test::init();
+
+        let email = format!("{}@cloudmaker.dev", Uuid::new_v4());
+        let user = User::create(&db::connection().unwrap(), UserMessage { email: email.clone(), password: "test".to_string() })
+            .expect("Failed to create user");
+
+        let redis_port = env::var("REDIS_PORT").expect("Redis port not set");
+        let redis_host = env::var("REDIS_HOST").expect("Redis host not set");
+
+        let mut app = test::init_service(
+            App::new()
+                .wrap(RedisSession::new(format!("{}:{}", redis_host, redis_port), &[0; 32]))
+                .configure(init_routes)
+        ).await;
+
+        let request_body = json!({
+            "email": email,
+            "password": "wrong",
+        });
+
+        let req = TestRequest::post().uri("/sign-in").set_json(&request_body).to_request();
+        let resp = test::call_service(&mut app, req).await;
+        let wrong_password_status = resp.status();
+        let wrong_password_body = test::read_body(resp).await;
+
+        let request_body = json!({
+            "email": format!("{}@cloudmaker.dev", Uuid::new_v4()),
+            "password": "wrong",
+        });
+
+        let req = TestRequest::post().uri("/sign-in").set_json(&request_body).to_request();
+        let resp = test::call_service(&mut app, req).await;
+        let unknown_email_status = resp.status();
+        let unknown_email_body = test::read_body(resp).await;
+
+        User::delete(user.id).expect("Failed to delete user");
+
+        assert_eq!(401, wrong_password_status.as_u16(), "Wrong password should be rejected");
+        assert_eq!(wrong_password_status, unknown_email_status, "Unknown email should get the same status as a wrong password");
+        assert_eq!(wrong_password_body, unknown_email_body, "Unknown email should get the same body as a wrong password");
+    }
+
+    #[actix_rt::test]
+    async fn test_register_rejects_bad_tokens_uniformly() {
+        crate::test::init();
+
+        let email = format!("{}@cloudmaker.dev", Uuid::new_v4());
+        let conn = db::connection().expect("Failed to get db connection");
+        let (token, secret) = EmailVerificationToken::create(&conn, EmailVerificationTokenMessage { id: None, email: email.clone() })
+            .expect("Failed to create token");
+        let token_string = hex::encode(&secret);
+
+        let redis_port = env::var("REDIS_PORT").expect("Redis port not set");
+        let redis_host = env::var("REDIS_HOST").expect("Redis host not set");
+
+        let mut app = test::init_service(
+            App::new()
+                .wrap(RedisSession::new(format!("{}:{}", redis_host, redis_port), &[0; 32]))
+                .configure(init_routes)
+        ).await;
+
+        let mut responses = Vec::new();
+
+        let request_bodies = vec![
+            json!({ "token": "not hex", "email": email, "password": "test" }),
+            json!({ "token": hex::encode([0u8; 32]), "email": email, "password": "test" }),
+            json!({ "token": token_string, "email": "someone@cloudmaker.dev", "password": "test" }),
+            // The stored hash is not a token
+            json!({ "token": hex::encode(&token.id), "email": email, "password": "test" }),
+        ];
+
+        for request_body in request_bodies {
+            let req = TestRequest::post().uri("/register").set_json(&request_body).to_request();
+            let resp = test::call_service(&mut app, req).await;
+            let status = resp.status().as_u16();
+            let body: Value = test::read_body_json(resp).await;
+            responses.push((status, body));
+        }
+
+        diesel::update(email_verification_token::table)
+            .filter(email_verification_token::id.eq(&token.id))
+            .set(email_verification_token::expires_at.eq(Utc::now().naive_utc() - Duration::hours(1)))
+            .execute(&conn)
+            .expect("Failed to expire token");
+
+        let request_body = json!({ "token": token_string, "email": email, "password": "test" });
+        let req = TestRequest::post().uri("/register").set_json(&request_body).to_request();
+        let resp = test::call_service(&mut app, req).await;
+        let status = resp.status().as_u16();
+        let body: Value = test::read_body_json(resp).await;
+        responses.push((status, body));
+
+        assert!(EmailVerificationToken::delete_expired().expect("Failed to delete expired tokens") >= 1);
+        assert_eq!(0, EmailVerificationToken::delete(&token.id).expect("Failed to delete token"), "Expired token should be cleaned up");
+
+        for (status, body) in responses {
+            assert_eq!(403, status, "Bad token should be rejected");
+            assert_eq!(json!({ "message": "Invalid token" }), body, "Bad tokens should all get the same response");
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_invite_and_register() {
+        let mailer = crate::test::mailer();
+
+        let email = format!("{}@cloudmaker.dev", Uuid::new_v4());
+
+        let redis_port = env::var("REDIS_PORT").expect("Redis port not set");
+        let redis_host = env::var("REDIS_HOST").expect("Redis host not set");
+
+        let mut app = test::init_service(
+            App::new()
+                .app_data(mailer.clone())
+                .wrap(RedisSession::new(format!("{}:{}", redis_host, redis_port), &[0; 32]))
+                .configure(init_routes)
+        ).await;
+
+        let req = TestRequest::post().uri("/invite").set_json(&json!({ "email": email })).to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert!(resp.status().is_success(), "Failed to invite");
+        assert!(MemoryTransport::sent_to(&email).is_empty(), "Invitation should be queued, not sent from the request");
+
+        outbound_email::process_due_for(&mailer, &email).await.expect("Failed to process the outbox");
+
+        assert_eq!(1, MemoryTransport::sent_to(&email).len(), "Expected one invitation email");
+        let token = link_token(&email);
+
+        let payloads = outbox_payloads(&email);
+        assert_eq!(1, payloads.len(), "Expected the invitation in the outbox");
+        assert!(!payloads[0].contains(&token), "The outbox should not hold the invitation token");
+
+        let request_body = json!({ "token": token, "email": email, "password": "test" });
+        let req = TestRequest::post().uri("/register").set_json(&request_body).to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert!(resp.status().is_success(), "Failed to register with the invitation token");
+
+        let user = User::find_by_email(email).expect("Registered user not found");
+        User::delete(user.id).expect("Failed to delete user");
+
+        let req = TestRequest::post().uri("/register").set_json(&request_body).to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(403, resp.status().as_u16(), "Invitation token should only work once");
+    }
+
+    fn outbox_payloads(email: &str) -> Vec<String> {
+        use crate::schema::outbound_email;
+
+        let conn = db::connection().expect("Failed to get db connection");
+        outbound_email::table
+            .select(outbound_email::payload)
+            .filter(outbound_email::payload.like(format!("%{}%", email)))
+            .load(&conn)
+            .expect("Failed to load the outbox")
+    }
+
+    fn link_token(email: &str) -> String {
+        let sent = MemoryTransport::sent_to(email);
+        let text = sent.last().expect("No email sent").text().expect("Email has no text part");
+        let link = text.split_whitespace()
+            .find(|word| word.starts_with("http"))
+            .expect("Email has no link");
+        let link = Url::parse(link).expect("Link is not a valid url");
+        let (_, token) = link.query_pairs()
+            .find(|(key, _)| key == "token")
+            .expect("Link has no token");
+        token.into_owned()
+    }
+
+    #[actix_rt::test]
+    async fn test_resend_invite_is_throttled_and_reuses_token() {
+        let mailer = crate::test::mailer();
+
+        let email = format!("{}@cloudmaker.dev", Uuid::new_v4());
+
+        let redis_port = env::var("REDIS_PORT").expect("Redis port not set");
+        let redis_host = env::var("REDIS_HOST").expect("Redis host not set");
+
+        let mut app = test::init_service(
+            App::new()
+                .app_data(mailer.clone())
+                .wrap(RedisSession::new(format!("{}:{}", redis_host, redis_port), &[0; 32]))
+                .configure(init_routes)
+        ).await;
+
+        let req = TestRequest::post().uri("/invite").set_json(&json!({ "email": email })).to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert!(resp.status().is_success(), "Failed to invite");
+
+        outbound_email::process_due_for(&mailer, &email).await.expect("Failed to process the outbox");
+        let token = link_token(&email);
+
+        let req = TestRequest::post().uri("/invite/resend").set_json(&json!({ "email": email })).to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(429, resp.status().as_u16(), "Resend within the cooldown should be throttled");
+        let retry_after: usize = resp.headers().get("Retry-After")
+            .expect("Throttled response has no Retry-After header")
+            .to_str().unwrap()
+            .parse().unwrap();
+        assert!(retry_after > 0);
+
+        // Skip the cooldown
+        let mut cache = cache::connection().expect("Failed to get redis connection");
+        let _: () = cache.del(format!("throttle.invite.email.{}", email)).unwrap();
+
+        let req = TestRequest::post().uri("/invite/resend").set_json(&json!({ "email": email })).to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert!(resp.status().is_success(), "Failed to resend the invitation");
+
+        outbound_email::process_due_for(&mailer, &email).await.expect("Failed to process the outbox");
+        assert_eq!(2, MemoryTransport::sent_to(&email).len(), "Expected the invitation to be sent again");
+        assert_eq!(token, link_token(&email), "Resend should reuse the unexpired token");
+
+        let _: () = cache.del(format!("throttle.invite.daily.{}", email)).unwrap();
+        let conn = db::connection().expect("Failed to get db connection");
+        let token = EmailVerificationToken::find_unexpired(&conn, &email).expect("Invitation not found");
+        EmailVerificationToken::delete(&token.id).expect("Failed to delete token");
+    }
+
+    #[actix_rt::test]
+    async fn test_magic_link_signs_in_once() {
+        let mailer = crate::test::mailer();
+
+        let email = format!("{}@cloudmaker.dev", Uuid::new_v4());
+        let user = User::create(&db::connection().unwrap(), UserMessage { email: email.clone(), password: "test".to_string() })
+            .expect("Failed to create user");
+
+        let redis_port = env::var("REDIS_PORT").expect("Redis port not set");
+        let redis_host = env::var("REDIS_HOST").expect("Redis host not set");
+
+        let mut app = test::init_service(
+            App::new()
+                .app_data(mailer.clone())
+                .wrap(RedisSession::new(format!("{}:{}", redis_host, redis_port), &[0; 32]))
+                .configure(init_routes)
+        ).await;
+
+        let req = TestRequest::post().uri("/sign-in/magic-link").set_json(&json!({ "email": email })).to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert!(resp.status().is_success(), "Failed to request a magic link");
+        let known_body: Value = test::read_body_json(resp).await;
+
+        let unknown_email = format!("{}@cloudmaker.dev", Uuid::new_v4());
+        let req = TestRequest::post().uri("/sign-in/magic-link").set_json(&json!({ "email": unknown_email })).to_request();
+        let resp = test::call_service(&mut app, req).await;
+        let unknown_body: Value = test::read_body_json(resp).await;
+        assert_eq!(known_body, unknown_body, "Unknown email should get the same response");
+
+        outbound_email::process_due_for(&mailer, &email).await.expect("Failed to process the outbox");
+        outbound_email::process_due_for(&mailer, &unknown_email).await.expect("Failed to process the outbox");
+
+        assert!(MemoryTransport::sent_to(&unknown_email).is_empty(), "Unknown email should not get a magic link");
+        let sent = MemoryTransport::sent_to(&email);
+        assert_eq!(1, sent.len(), "Expected one magic link email");
+        let text = sent[0].text().expect("Magic link email has no text part");
+        let link = text.split_whitespace()
+            .find(|word| word.starts_with("http"))
+            .expect("Magic link email has no link");
+        let link = Url::parse(link).expect("Magic link is not a valid url");
+
+        let req = TestRequest::get().uri(link.path()).to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert!(resp.status().is_success(), "Failed to sign in with the magic link");
+        assert!(resp.response().cookies().next().is_some(), "Signing in should set the session cookie");
+        let signed_in: Value = test::read_body_json(resp).await;
+        assert_eq!(json!(user.id), signed_in["id"]);
+
+        let req = TestRequest::get().uri(link.path()).to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(403, resp.status().as_u16(), "Magic link should only work once");
+
+        let mut cache = cache::connection().expect("Failed to get redis connection");
+        let _: () = cache.del(format!("throttle.magic_link.email.{}", email)).unwrap();
+        let _: () = cache.del(format!("throttle.magic_link.daily.{}", email)).unwrap();
+        let _: () = cache.del(format!("throttle.magic_link.email.{}", unknown_email)).unwrap();
+        let _: () = cache.del(format!("throttle.magic_link.daily.{}", unknown_email)).unwrap();
+        User::delete(user.id).expect("Failed to delete user");
+    }
+
+    #[actix_rt::test]
+    async fn test_password_reset_signs_out_everywhere() {
+        let mailer = crate::test::mailer();
+
+        let email = format!("{}@cloudmaker.dev", Uuid::new_v4());
+        let unknown_email = format!("{}@cloudmaker.dev", Uuid::new_v4());
+        let user = User::create(&db::connection().unwrap(), UserMessage { email: email.clone(), password: "test".to_string() })
+            .expect("Failed to create user");
+
+        let redis_port = env::var("REDIS_PORT").expect("Redis port not set");
+        let redis_host = env::var("REDIS_HOST").expect("Redis host not set");
+
+        let mut app = test::init_service(
+            App::new()
+                .app_data(mailer.clone())
+                .wrap(RedisSession::new(format!("{}:{}", redis_host, redis_port), &[0; 32]))
+                .configure(init_routes)
+        ).await;
+
+        let request_body = json!({ "email": email, "password": "test" });
+        let req = TestRequest::post().uri("/sign-in").set_json(&request_body).to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert!(resp.status().is_success(), "Failed to sign in");
+        let cookie = resp.response().cookies().next().expect("No session cookie").into_owned();
+
+        let req = TestRequest::post().uri("/password-reset/request").set_json(&json!({ "email": email })).to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert!(resp.status().is_success(), "Failed to request a password reset");
+        let known_body: Value = test::read_body_json(resp).await;
+
+        let req = TestRequest::post().uri("/password-reset/request").set_json(&json!({ "email": unknown_email })).to_request();
+        let resp = test::call_service(&mut app, req).await;
+        let unknown_body: Value = test::read_body_json(resp).await;
+        assert_eq!(known_body, unknown_body, "Unknown email should get the same response");
+
+        outbound_email::process_due_for(&mailer, &email).await.expect("Failed to process the outbox");
+        outbound_email::process_due_for(&mailer, &unknown_email).await.expect("Failed to process the outbox");
+        assert_eq!(1, MemoryTransport::sent_to(&email).len(), "Expected one password reset email");
+        assert!(MemoryTransport::sent_to(&unknown_email).is_empty(), "Unknown email should not get a password reset email");
+        let token = link_token(&email);
+
+        let request_body = json!({ "token": hex::encode([0u8; 32]), "password": "new" });
+        let req = TestRequest::post().uri("/password-reset/confirm").set_json(&request_body).to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(403, resp.status().as_u16(), "Bad token should be rejected");
+
+        let request_body = json!({ "token": token, "password": "new" });
+        let req = TestRequest::post().uri("/password-reset/confirm").set_json(&request_body).to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert!(resp.status().is_success(), "Failed to reset the password");
+
+        let req = TestRequest::post().uri("/password-reset/confirm").set_json(&request_body).to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(403, resp.status().as_u16(), "Password reset token should only work once");
+
+        let req = TestRequest::get().uri("/who-am-i").cookie(cookie).to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(401, resp.status().as_u16(), "Password reset should sign out existing sessions");
+
+        let req = TestRequest::post().uri("/sign-in").set_json(&json!({ "email": email, "password": "test" })).to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(401, resp.status().as_u16(), "Old password should no longer work");
+
+        let req = TestRequest::post().uri("/sign-in").set_json(&json!({ "email": email, "password": "new" })).to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert!(resp.status().is_success(), "Failed to sign in with the new password");
+
+        User::delete(user.id).expect("Failed to delete user");
+    }
+}
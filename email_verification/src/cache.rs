@@ -9,8 +9,10 @@ pub type CacheConnection = r2d2::PooledConnection<Client>;
 
 lazy_static! {
     static ref POOL: Pool = {
-        let redis_url = env::var("REDIS_URL").expect("Redis url not set");
-        let client = redis::Client::open(redis_url).expect("Failed to create redis client");
+        let redis_port = env::var("REDIS_PORT").expect("Redis port not set");
+        let redis_host = env::var("REDIS_HOST").expect("Redis host not set");
+        let client = redis::Client::open(format!("redis://{}:{}", redis_host, redis_port))
+            .expect("Failed to create redis client");
         Pool::new(client).expect("Failed to create redis pool")
     };
 }
@@ -24,5 +26,5 @@ pub fn init() {
 
 pub fn connection() -> Result<CacheConnection, ApiError> {
     POOL.get()
-        .map_err(|e| ApiError::new(500, format!("Failed getting db connection: {}", e)))
+        .map_err(|e| ApiError::new(500, format!("Failed getting redis connection: {}", e)))
 }
@@ -0,0 +1,122 @@
+use crate::api_error::ApiError;
+use actix_service::{Service, Transform};
+use actix_session::UserSession;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::Method;
+use actix_web::Error;
+use futures::future::{err, ok, Either, Ready};
+use std::rc::Rc;
+use std::task::{Context, Poll};
+use uuid::Uuid;
+
+const HEADER_NAME: &str = "X-CSRF-Token";
+
+// Double submit protection for requests authenticated by the session cookie.
+// State changing requests have to repeat the token from GET /csrf-token in the
+// X-CSRF-Token header, which a cross site form or request can't read or set.
+pub struct Csrf {
+    exempt: Rc<Vec<String>>,
+}
+
+impl Csrf {
+    pub fn new() -> Self {
+        Csrf { exempt: Rc::new(Vec::new()) }
+    }
+
+    // Either an exact path, or a prefix like /webhooks/* for everything below it
+    pub fn exempt<T: Into<String>>(mut self, path: T) -> Self {
+        Rc::make_mut(&mut self.exempt).push(path.into());
+        self
+    }
+}
+
+impl<S, B> Transform<S> for Csrf
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = CsrfMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ok(CsrfMiddleware { service, exempt: self.exempt.clone() })
+    }
+}
+
+pub struct CsrfMiddleware<S> {
+    service: S,
+    exempt: Rc<Vec<String>>,
+}
+
+impl<S, B> Service for CsrfMiddleware<S>
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = Either<S::Future, Ready<Result<Self::Response, Self::Error>>>;
+
+    fn poll_ready(&mut self, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: ServiceRequest) -> Self::Future {
+        match self.check(&req) {
+            Ok(()) => Either::Left(self.service.call(req)),
+            Err(e) => Either::Right(err(e.into())),
+        }
+    }
+}
+
+impl<S> CsrfMiddleware<S> {
+    fn check(&self, req: &ServiceRequest) -> Result<(), ApiError> {
+        let is_safe = match *req.method() {
+            Method::GET | Method::HEAD | Method::OPTIONS => true,
+            _ => false,
+        };
+
+        if is_safe || self.exempt.iter().any(|pattern| is_exempt(pattern, req.path())) {
+            return Ok(());
+        }
+
+        // A sign in waiting for its two-factor code is protected as well
+        let session = req.get_session();
+        let user_id: Option<Uuid> = session.get("user_id")?;
+        let two_factor_user_id: Option<Uuid> = session.get("two_factor_user_id")?;
+        if user_id.is_none() && two_factor_user_id.is_none() {
+            return Ok(());
+        }
+
+        let expected: Option<String> = session.get("csrf_token")?;
+        let actual = req.headers()
+            .get(HEADER_NAME)
+            .and_then(|token| token.to_str().ok());
+
+        match (expected, actual) {
+            (Some(expected), Some(actual)) if constant_time_eq(expected.as_bytes(), actual.as_bytes()) => Ok(()),
+            _ => Err(ApiError::new(403, "Invalid CSRF token")),
+        }
+    }
+}
+
+fn is_exempt(pattern: &str, path: &str) -> bool {
+    if pattern.ends_with("/*") {
+        path.starts_with(&pattern[..pattern.len() - 1])
+    } else {
+        pattern == path
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+
+    a.iter().zip(b).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+}
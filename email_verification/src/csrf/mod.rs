@@ -0,0 +1,6 @@
+mod middleware;
+mod routes;
+mod tests;
+
+pub use middleware::Csrf;
+pub use routes::init_routes;
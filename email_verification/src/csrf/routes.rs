@@ -0,0 +1,23 @@
+use crate::api_error::ApiError;
+use actix_web::{get, web, HttpResponse};
+use actix_session::Session;
+use rand::Rng;
+use serde_json::json;
+
+#[get("/csrf-token")]
+async fn csrf_token(session: Session) -> Result<HttpResponse, ApiError> {
+    let token = match session.get::<String>("csrf_token")? {
+        Some(token) => token,
+        None => {
+            let token = hex::encode(rand::thread_rng().gen::<[u8; 32]>());
+            session.set("csrf_token", &token)?;
+            token
+        },
+    };
+
+    Ok(HttpResponse::Ok().json(json!({ "csrf_token": token })))
+}
+
+pub fn init_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(csrf_token);
+}
@@ -0,0 +1,90 @@
+
+#[cfg(test)]
+mod tests {
+    use crate::auth;
+    use crate::csrf::{init_routes, Csrf};
+    use crate::db;
+    use crate::two_factor::{self, Totp};
+    use crate::user::{User, UserMessage};
+    use actix_redis::RedisSession;
+    use actix_web::{test::{self, TestRequest}, web, App, HttpResponse};
+    use serde_json::{json, Value};
+    use std::env;
+    use uuid::Uuid;
+
+    fn create_user() -> User {
+        let email = format!("{}@cloudmaker.dev", Uuid::new_v4());
+        User::create(&db::connection().unwrap(), UserMessage { email, password: "test".to_string() })
+            .expect("Failed to create user")
+    }
+
+    #[actix_rt::test]
+    async fn test_csrf_token_is_required() {
+        crate::test::init();
+        let user = create_user();
+        let two_factor_user = create_user();
+        User::set_totp(two_factor_user.id, Some(Totp::generate().encrypt().unwrap()), true).unwrap();
+
+        let redis_port = env::var("REDIS_PORT").expect("Redis port not set");
+        let redis_host = env::var("REDIS_HOST").expect("Redis host not set");
+
+        let mut app = test::init_service(
+            App::new()
+                .wrap(Csrf::new().exempt("/hooks/*").exempt("/exempt"))
+                .wrap(RedisSession::new(format!("{}:{}", redis_host, redis_port), &[0; 32]))
+                .configure(init_routes)
+                .configure(auth::init_routes)
+                .configure(two_factor::init_routes)
+                .route("/write", web::post().to(|| async { HttpResponse::Ok().finish() }))
+                .route("/exempt", web::post().to(|| async { HttpResponse::Ok().finish() }))
+                .route("/exempt/nested", web::post().to(|| async { HttpResponse::Ok().finish() }))
+                .route("/hooks/email", web::post().to(|| async { HttpResponse::Ok().finish() }))
+        ).await;
+
+        let req = TestRequest::post().uri("/write").to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert!(resp.status().is_success(), "Signed out requests don't need a token");
+
+        let req = TestRequest::post().uri("/sign-in").set_json(&json!({ "email": user.email, "password": "test" })).to_request();
+        let resp = test::call_service(&mut app, req).await;
+        let cookie = resp.response().cookies().next().expect("No session cookie").into_owned();
+
+        let req = TestRequest::post().uri("/write").cookie(cookie.clone()).to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(403, resp.status().as_u16(), "Missing token should be rejected");
+
+        let req = TestRequest::get().uri("/csrf-token").cookie(cookie.clone()).to_request();
+        let resp = test::call_service(&mut app, req).await;
+        let body: Value = test::read_body_json(resp).await;
+        let token = body["csrf_token"].as_str().expect("No csrf token").to_string();
+
+        let req = TestRequest::post().uri("/write").cookie(cookie.clone()).header("X-CSRF-Token", "wrong").to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(403, resp.status().as_u16(), "Wrong token should be rejected");
+
+        let req = TestRequest::post().uri("/write").cookie(cookie.clone()).header("X-CSRF-Token", token.as_str()).to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert!(resp.status().is_success(), "Request with the token should be accepted");
+
+        for path in &["/exempt", "/hooks/email"] {
+            let req = TestRequest::post().uri(path).cookie(cookie.clone()).to_request();
+            let resp = test::call_service(&mut app, req).await;
+            assert!(resp.status().is_success(), "{} should be exempt", path);
+        }
+
+        let req = TestRequest::post().uri("/exempt/nested").cookie(cookie.clone()).to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(403, resp.status().as_u16(), "An exact exemption should not cover the paths below it");
+
+        let req = TestRequest::post().uri("/sign-in").set_json(&json!({ "email": two_factor_user.email, "password": "test" })).to_request();
+        let resp = test::call_service(&mut app, req).await;
+        let cookie = resp.response().cookies().next().expect("No session cookie").into_owned();
+
+        let req = TestRequest::post().uri("/sign-in/2fa").cookie(cookie).set_json(&json!({ "code": "000000" })).to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(403, resp.status().as_u16(), "A sign in waiting for its two-factor code should need the token");
+
+        User::delete(user.id).unwrap();
+        User::delete(two_factor_user.id).unwrap();
+    }
+}
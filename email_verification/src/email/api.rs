@@ -1,15 +1,14 @@
 use crate::api_error::ApiError;
-use serde::Serialize;
-use std::collections::HashMap;
+use crate::email::Mailer;
+use actix_web::web;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
-lazy_static::lazy_static! {
-    static ref SENDINBLUE_API_KEY: String = std::env::var("SENDINBLUE_API_KEY").unwrap_or("".to_string());
-}
-
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Contact {
-    email: String,
-    name: Option<String>,
+    pub(super) email: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(super) name: Option<String>,
 }
 
 impl Contact {
@@ -24,14 +23,38 @@ impl<T: Into<String>> From<T> for Contact {
     }
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Attachment {
+    pub(super) name: String,
+    pub(super) content_type: String,
+    // Base64 encoded
+    pub(super) content: String,
+    // Only set for inline images, which the html refers to as cid:<content_id>
+    pub(super) content_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Email {
-    sender: Contact,
+    pub(super) sender: Contact,
     #[serde(rename = "to")]
-    recipients: Vec<Contact>,
-    subject: String,
+    pub(super) recipients: Vec<Contact>,
+    pub(super) subject: String,
     #[serde(rename = "htmlContent")]
-    html: Option<String>
+    pub(super) html: Option<String>,
+    #[serde(rename = "textContent")]
+    pub(super) text: Option<String>,
+    #[serde(default)]
+    pub(super) cc: Vec<Contact>,
+    #[serde(default)]
+    pub(super) bcc: Vec<Contact>,
+    #[serde(default)]
+    pub(super) reply_to: Option<Contact>,
+    #[serde(default)]
+    pub(super) headers: BTreeMap<String, String>,
+    #[serde(default)]
+    pub(super) tags: Vec<String>,
+    #[serde(default)]
+    pub(super) attachments: Vec<Attachment>,
 }
 
 impl Email {
@@ -41,6 +64,13 @@ impl Email {
             recipients: Vec::new(),
             subject: "".to_string(),
             html: None,
+            text: None,
+            cc: Vec::new(),
+            bcc: Vec::new(),
+            reply_to: None,
+            headers: BTreeMap::new(),
+            tags: Vec::new(),
+            attachments: Vec::new(),
         }
     }
 
@@ -49,6 +79,57 @@ impl Email {
         self
     }
 
+    pub fn add_cc<T: Into<Contact>>(mut self, recipient: T) -> Self {
+        self.cc.push(recipient.into());
+        self
+    }
+
+    pub fn add_bcc<T: Into<Contact>>(mut self, recipient: T) -> Self {
+        self.bcc.push(recipient.into());
+        self
+    }
+
+    pub fn set_reply_to<T: Into<Contact>>(mut self, reply_to: T) -> Self {
+        self.reply_to = Some(reply_to.into());
+        self
+    }
+
+    pub fn add_header<N: Into<String>, V: Into<String>>(mut self, name: N, value: V) -> Self {
+        self.headers.insert(name.into(), value.into());
+        self
+    }
+
+    pub fn add_tag<T: Into<String>>(mut self, tag: T) -> Self {
+        self.tags.push(tag.into());
+        self
+    }
+
+    pub fn add_attachment<N: Into<String>, C: Into<String>>(mut self, name: N, content_type: C, content: &[u8]) -> Self {
+        self.attachments.push(Attachment {
+            name: name.into(),
+            content_type: content_type.into(),
+            content: base64::encode(content),
+            content_id: None,
+        });
+        self
+    }
+
+    // Only the transports that send MIME messages support inline images, sendinblue refuses them
+    pub fn add_inline_image<I, N, C>(mut self, content_id: I, name: N, content_type: C, content: &[u8]) -> Self
+    where
+        I: Into<String>,
+        N: Into<String>,
+        C: Into<String>,
+    {
+        self.attachments.push(Attachment {
+            name: name.into(),
+            content_type: content_type.into(),
+            content: base64::encode(content),
+            content_id: Some(content_id.into()),
+        });
+        self
+    }
+
     pub fn set_subject<T: Into<String>>(mut self, subject: T) -> Self {
         self.subject = subject.into();
         self
@@ -59,26 +140,34 @@ impl Email {
         self
     }
 
-    pub fn send(self) -> Result<String, ApiError> {
-        let client = reqwest::Client::new();
-        let mut response = client.post("https://api.sendinblue.com/v3/smtp/email")
-            .header("Accept", "application/json")
-            .header("api-key", SENDINBLUE_API_KEY.as_str())
-            .json(&self)
-            .send()
-            .map_err(|e| ApiError::new(500, format!("Failed to send email: {}", e)))?;
-
-        let status = response.status().as_u16();
-        let mut body: HashMap<String, String> = response
-            .json()
-            .map_err(|e| ApiError::new(500, format!("Failed to read sendinblue response: {}", e)))?;
-
-        match status {
-            201 => Ok(body.remove("messageId").unwrap_or("".to_string())),
-            _ => {
-                let message = body.remove("message").unwrap_or("Unknown error".to_string());
-                Err(ApiError::new(500, format!("Failed to send email: {}", message)))
-            }
-        }
+    pub fn set_text<T: Into<String>>(mut self, text: T) -> Self {
+        self.text = Some(text.into());
+        self
+    }
+
+    pub fn subject(&self) -> &str {
+        &self.subject
+    }
+
+    pub fn html(&self) -> Option<&str> {
+        self.html.as_deref()
+    }
+
+    pub fn text(&self) -> Option<&str> {
+        self.text.as_deref()
+    }
+
+    pub fn has_recipient(&self, email: &str) -> bool {
+        self.recipients.iter()
+            .chain(self.cc.iter())
+            .chain(self.bcc.iter())
+            .any(|recipient| recipient.email == email)
+    }
+
+    // Sends on the blocking thread pool, so a slow mail server doesn't hold up the
+    // worker. Returns the message id given by the transport.
+    pub async fn send(self, mailer: web::Data<Mailer>) -> Result<String, ApiError> {
+        let message_id = web::block(move || mailer.transport().send(&self)).await?;
+        Ok(message_id)
     }
 }
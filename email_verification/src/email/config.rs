@@ -0,0 +1,117 @@
+use crate::email::Contact;
+use lettre::EmailAddress;
+use reqwest::Url;
+use std::env;
+use std::time::Duration;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Timeouts {
+    pub connect: Duration,
+    pub read: Duration,
+}
+
+#[derive(Clone, Debug)]
+pub enum TransportConfig {
+    Sendinblue { api_key: String, api_url: Url },
+    // STARTTLS is required unless starttls is false, which is only meant for local mail catchers
+    Smtp { host: String, port: u16, starttls: bool, credentials: Option<(String, String)> },
+    File { path: String },
+    Memory,
+}
+
+#[derive(Clone, Debug)]
+pub struct EmailConfig {
+    pub sender: Contact,
+    pub reply_to: Option<Contact>,
+    pub transport: TransportConfig,
+    pub timeouts: Timeouts,
+    // The public url of this api, for links that are handled by it directly
+    pub app_base_url: Url,
+    // Links that need a page, like a form for a new password, go to the frontend, see FRONTEND_ROUTES
+    pub frontend_url: Url,
+    pub template_dir: String,
+    // The bearer token or basic auth password set up for the provider's delivery webhooks.
+    // Webhooks are rejected without it.
+    pub webhook_secret: Option<String>,
+}
+
+impl EmailConfig {
+    pub fn from_env() -> Result<Self, String> {
+        EmailConfig::from_vars(|key| env::var(key).ok().filter(|value| !value.is_empty()))
+    }
+
+    pub fn from_vars<F: Fn(&str) -> Option<String>>(var: F) -> Result<Self, String> {
+        let sender_address = var("EMAIL_SENDER_ADDRESS").ok_or("EMAIL_SENDER_ADDRESS not set")?;
+        let sender = match var("EMAIL_SENDER_NAME") {
+            Some(name) => Contact::new(address(&sender_address)?, name),
+            None => Contact::from(address(&sender_address)?),
+        };
+
+        let reply_to = match var("EMAIL_REPLY_TO") {
+            Some(reply_to) => Some(Contact::from(address(&reply_to)?)),
+            None => None,
+        };
+
+        let transport = match var("EMAIL_TRANSPORT").as_ref().map(String::as_str).unwrap_or("sendinblue") {
+            "sendinblue" => TransportConfig::Sendinblue {
+                api_key: var("SENDINBLUE_API_KEY").ok_or("SENDINBLUE_API_KEY not set")?,
+                api_url: url(&var("SENDINBLUE_API_URL").unwrap_or("https://api.sendinblue.com/v3".to_string()))?,
+            },
+            "smtp" => TransportConfig::Smtp {
+                host: var("SMTP_HOST").ok_or("SMTP_HOST not set")?,
+                port: number(&var, "SMTP_PORT", 587)?,
+                starttls: var("SMTP_STARTTLS").map(|starttls| starttls != "false").unwrap_or(true),
+                credentials: match (var("SMTP_USERNAME"), var("SMTP_PASSWORD")) {
+                    (Some(username), Some(password)) => Some((username, password)),
+                    (None, None) => None,
+                    _ => return Err("SMTP_USERNAME and SMTP_PASSWORD must be set together".to_string()),
+                },
+            },
+            "file" => TransportConfig::File {
+                path: var("EMAIL_FILE_PATH").unwrap_or("emails.mbox".to_string()),
+            },
+            "memory" => TransportConfig::Memory,
+            name => return Err(format!("Unknown email transport: {}", name)),
+        };
+
+        let timeouts = Timeouts {
+            connect: Duration::from_secs(number(&var, "EMAIL_CONNECT_TIMEOUT", 5)?),
+            read: Duration::from_secs(number(&var, "EMAIL_READ_TIMEOUT", 30)?),
+        };
+
+        Ok(EmailConfig {
+            sender,
+            reply_to,
+            transport,
+            timeouts,
+            app_base_url: url(&var("APP_BASE_URL").ok_or("APP_BASE_URL not set")?)?,
+            frontend_url: url(&var("FRONTEND_URL").ok_or("FRONTEND_URL not set")?)?,
+            template_dir: var("EMAIL_TEMPLATE_DIR").unwrap_or("templates/email".to_string()),
+            webhook_secret: var("EMAIL_WEBHOOK_SECRET"),
+        })
+    }
+}
+
+fn address(address: &str) -> Result<String, String> {
+    EmailAddress::new(address.to_string())
+        .map(|_| address.to_string())
+        .map_err(|_| format!("{} is not a valid email address", address))
+}
+
+fn url(url: &str) -> Result<Url, String> {
+    match Url::parse(url) {
+        Ok(url) if url.scheme() == "http" || url.scheme() == "https" => Ok(url),
+        _ => Err(format!("{} is not a valid http url", url)),
+    }
+}
+
+fn number<F, T>(var: &F, key: &str, default: T) -> Result<T, String>
+where
+    F: Fn(&str) -> Option<String>,
+    T: std::str::FromStr,
+{
+    match var(key) {
+        Some(value) => value.parse().map_err(|_| format!("{} must be a number", key)),
+        None => Ok(default),
+    }
+}
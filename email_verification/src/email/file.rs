@@ -0,0 +1,49 @@
+use crate::api_error::ApiError;
+use crate::email::{mime, Email, EmailTransport};
+use chrono::Utc;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::sync::Mutex;
+
+// Appends every email to an mbox file, so they can be read with any mail client
+pub struct FileTransport {
+    path: Mutex<String>,
+}
+
+impl FileTransport {
+    pub fn new(path: String) -> Self {
+        FileTransport { path: Mutex::new(path) }
+    }
+}
+
+impl EmailTransport for FileTransport {
+    fn send(&self, email: &Email) -> Result<String, ApiError> {
+        let message = mime::build(email)?;
+        let message_id = message.message_id().to_string();
+        let message = message.message_to_string()
+            .map_err(|e| ApiError::new(500, format!("Failed to render email: {}", e)))?;
+
+        let mut entry = format!("From {} {}\n", email.sender.email, Utc::now().format("%a %b %e %T %Y"));
+        for line in message.lines() {
+            // Lines that look like the start of a new message have to be quoted
+            if line.trim_start_matches('>').starts_with("From ") {
+                entry.push('>');
+            }
+            entry.push_str(line);
+            entry.push('\n');
+        }
+        entry.push('\n');
+
+        let path = self.path.lock()
+            .map_err(|_| ApiError::new(500, "Email file lock poisoned"))?;
+
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path.as_str())
+            .and_then(|mut file| file.write_all(entry.as_bytes()))
+            .map_err(|e| ApiError::new(500, format!("Failed to write email: {}", e)))?;
+
+        Ok(message_id)
+    }
+}
@@ -0,0 +1,96 @@
+use crate::api_error::ApiError;
+use crate::email::{
+    Email, EmailConfig, EmailTransport, FileTransport, MemoryTransport, SendinblueTransport,
+    SmtpTransport, Templates, TransportConfig,
+};
+use actix_web::HttpRequest;
+use reqwest::Url;
+use serde::Serialize;
+
+// The pages the frontend has to serve for the links in emails. Each takes the token from the
+// query, and submits it to the api route next to it.
+pub const FRONTEND_ROUTES: &[(&str, &str)] = &[
+    // Asks for a password, and posts it with the token and email
+    ("/register", "/register"),
+    ("/account/email/confirm", "/me/email/confirm"),
+    // Asks for a new password, and posts it with the token
+    ("/password-reset", "/password-reset/confirm"),
+];
+
+// Everything needed to send email, built once at startup and shared through web::Data
+pub struct Mailer {
+    pub config: EmailConfig,
+    transport: Box<dyn EmailTransport>,
+    templates: Templates,
+}
+
+impl Mailer {
+    pub fn new(config: EmailConfig) -> Result<Self, String> {
+        let transport: Box<dyn EmailTransport> = match &config.transport {
+            TransportConfig::Sendinblue { api_key, api_url } => {
+                Box::new(SendinblueTransport::new(api_key.clone(), api_url.clone(), config.timeouts)?)
+            },
+            TransportConfig::Smtp { host, port, starttls, credentials } => {
+                Box::new(SmtpTransport::new(host, *port, *starttls, credentials.clone(), config.timeouts)?)
+            },
+            TransportConfig::File { path } => Box::new(FileTransport::new(path.clone())),
+            TransportConfig::Memory => Box::new(MemoryTransport),
+        };
+
+        let templates = Templates::load(&config.template_dir)?;
+
+        Ok(Mailer { config, transport, templates })
+    }
+
+    pub fn transport(&self) -> &dyn EmailTransport {
+        self.transport.as_ref()
+    }
+
+    pub fn templates(&self) -> &Templates {
+        &self.templates
+    }
+
+    // An email from the configured sender, with the subject and both bodies from the named template
+    pub fn compose<T: Serialize>(&self, template: &str, locale: &str, data: &T) -> Result<Email, ApiError> {
+        let rendered = self.templates.render(template, locale, data)?;
+
+        let mut email = Email::new(self.config.sender.clone())
+            .set_subject(rendered.subject)
+            .set_html(rendered.html)
+            .set_text(rendered.text);
+
+        if let Some(reply_to) = &self.config.reply_to {
+            email = email.set_reply_to(reply_to.clone());
+        }
+
+        Ok(email)
+    }
+
+    pub fn locale(&self, req: &HttpRequest) -> String {
+        self.templates.locale(req)
+    }
+
+    // Builds a link to a route of this api
+    pub fn link(&self, path: &str, params: &[(&str, &str)]) -> Result<String, ApiError> {
+        Ok(build_link(&self.config.app_base_url, path, params))
+    }
+
+    // Builds a link to one of the FRONTEND_ROUTES
+    pub fn frontend_link(&self, path: &str, params: &[(&str, &str)]) -> Result<String, ApiError> {
+        if !FRONTEND_ROUTES.iter().any(|(route, _)| *route == path) {
+            return Err(ApiError::new(500, format!("{} is not a frontend route", path)));
+        }
+
+        Ok(build_link(&self.config.frontend_url, path, params))
+    }
+}
+
+fn build_link(base_url: &Url, path: &str, params: &[(&str, &str)]) -> String {
+    let mut url = base_url.clone();
+    url.set_path(&format!("{}{}", url.path().trim_end_matches('/'), path));
+    if !params.is_empty() {
+        url.query_pairs_mut().clear().extend_pairs(params);
+    }
+
+    url.into_string()
+}
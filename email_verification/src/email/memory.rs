@@ -0,0 +1,30 @@
+use crate::api_error::ApiError;
+use crate::email::{Email, EmailTransport};
+use lazy_static::lazy_static;
+use std::sync::Mutex;
+use uuid::Uuid;
+
+lazy_static! {
+    static ref OUTBOX: Mutex<Vec<Email>> = Mutex::new(Vec::new());
+}
+
+// Keeps the emails instead of sending them, so tests can read them back
+pub struct MemoryTransport;
+
+impl MemoryTransport {
+    pub fn sent_to(email: &str) -> Vec<Email> {
+        OUTBOX.lock()
+            .map(|outbox| outbox.iter().filter(|sent| sent.has_recipient(email)).cloned().collect())
+            .unwrap_or_default()
+    }
+}
+
+impl EmailTransport for MemoryTransport {
+    fn send(&self, email: &Email) -> Result<String, ApiError> {
+        OUTBOX.lock()
+            .map_err(|_| ApiError::new(500, "Email outbox lock poisoned"))?
+            .push(email.clone());
+
+        Ok(Uuid::new_v4().to_string())
+    }
+}
@@ -0,0 +1,152 @@
+use crate::api_error::ApiError;
+use crate::email::{Attachment, Contact, Email};
+use lettre::SendableEmail;
+use lettre_email::{EmailBuilder, Header, Mailbox, MimeMessage, MimeMultipartType, PartBuilder};
+
+// Renders the email as a MIME message, for the transports that speak SMTP or write to disk.
+//
+// multipart/mixed
+//   multipart/related (only with inline images)
+//     multipart/alternative (text and html)
+//     inline images
+//   attachments
+pub fn build(email: &Email) -> Result<SendableEmail, ApiError> {
+    let mut builder = EmailBuilder::new()
+        .from(mailbox(&email.sender))
+        .subject(email.subject.as_str());
+
+    for recipient in &email.recipients {
+        builder = builder.to(mailbox(recipient));
+    }
+
+    for recipient in &email.cc {
+        builder = builder.cc(mailbox(recipient));
+    }
+
+    // Bcc recipients only go in the envelope
+    for recipient in &email.bcc {
+        builder = builder.bcc(mailbox(recipient));
+    }
+
+    if let Some(reply_to) = &email.reply_to {
+        builder = builder.reply_to(mailbox(reply_to));
+    }
+
+    for (name, value) in &email.headers {
+        builder = builder.header((name.as_str(), value.as_str()));
+    }
+
+    if !email.tags.is_empty() {
+        builder = builder.header(("X-Tags", email.tags.join(", ")));
+    }
+
+    let (inline_images, attachments): (Vec<&Attachment>, Vec<&Attachment>) = email.attachments
+        .iter()
+        .partition(|attachment| attachment.content_id.is_some());
+
+    let mut body = body(email);
+    if !inline_images.is_empty() {
+        body = related(body, inline_images.into_iter().map(attachment).collect());
+    }
+
+    builder = builder
+        .message_type(MimeMultipartType::Mixed)
+        .child(body);
+
+    for part in attachments {
+        builder = builder.child(attachment(part));
+    }
+
+    let message = builder.build()
+        .map_err(|e| ApiError::new(422, format!("Failed to build email: {}", e)))?;
+
+    Ok(message.into())
+}
+
+fn mailbox(contact: &Contact) -> Mailbox {
+    match &contact.name {
+        Some(name) => (contact.email.as_str(), name.as_str()).into(),
+        None => contact.email.as_str().into(),
+    }
+}
+
+fn text_part(body: &str, content_type: &str) -> MimeMessage {
+    PartBuilder::new()
+        .body(body)
+        .header(("Content-Type", format!("{}; charset=utf-8", content_type)))
+        .build()
+}
+
+fn body(email: &Email) -> MimeMessage {
+    match (&email.html, &email.text) {
+        (Some(html), Some(text)) => PartBuilder::new()
+            .message_type(MimeMultipartType::Alternative)
+            .child(text_part(text, "text/plain"))
+            .child(text_part(html, "text/html"))
+            .build(),
+        (Some(html), None) => text_part(html, "text/html"),
+        (None, Some(text)) => text_part(text, "text/plain"),
+        (None, None) => text_part("", "text/plain"),
+    }
+}
+
+// The email crate has no multipart/related type, so the header is set by hand
+fn related(body: MimeMessage, inline_images: Vec<MimeMessage>) -> MimeMessage {
+    let mut related = MimeMessage::new_blank_message();
+    let content_type = format!("multipart/related; boundary=\"{}\"", related.boundary);
+    related.headers.insert(Header::new("Content-Type".to_string(), content_type));
+    related.children.push(body);
+    related.children.extend(inline_images);
+    related
+}
+
+fn attachment(attachment: &Attachment) -> MimeMessage {
+    // Base64 lines have to be wrapped to stay within the SMTP line length limit
+    let content = attachment.content.as_bytes()
+        .chunks(76)
+        .map(|line| String::from_utf8_lossy(line).into_owned())
+        .collect::<Vec<_>>()
+        .join("\r\n");
+
+    let disposition = match attachment.content_id {
+        Some(_) => "inline",
+        None => "attachment",
+    };
+
+    let mut part = PartBuilder::new()
+        .body(content)
+        .header(("Content-Type", attachment.content_type.as_str()))
+        .header(("Content-Transfer-Encoding", "base64"))
+        .header(("Content-Disposition", content_disposition(disposition, &attachment.name)));
+
+    if let Some(content_id) = &attachment.content_id {
+        part = part.header(("Content-ID", format!("<{}>", content_id)));
+    }
+
+    part.build()
+}
+
+// Plain ascii names go in a quoted filename. Anything else also gets the RFC 2231 encoded
+// filename*, with a fallback that can't break out of the quotes or the header.
+fn content_disposition(disposition: &str, name: &str) -> String {
+    let fallback: String = name.chars()
+        .map(|c| match c {
+            '"' | '\\' => '_',
+            c if c.is_ascii() && !c.is_ascii_control() => c,
+            _ => '_',
+        })
+        .collect();
+
+    if fallback == name {
+        return format!("{}; filename=\"{}\"", disposition, name);
+    }
+
+    let encoded: String = name.bytes()
+        .map(|byte| match byte {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'!' | b'#' | b'$' | b'&' | b'+' | b'-' | b'.' | b'^' | b'_' | b'`' | b'|' | b'~' => (byte as char).to_string(),
+            _ => format!("%{:02X}", byte),
+        })
+        .collect();
+
+    format!("{}; filename=\"{}\"; filename*=utf-8''{}", disposition, fallback, encoded)
+}
@@ -1,3 +1,21 @@
 mod api;
+mod config;
+mod file;
+mod mailer;
+mod memory;
+mod mime;
+mod sendinblue;
+mod smtp;
+mod template;
+mod tests;
+mod transport;
 
-pub use api::{Email, Contact};
+pub use api::{Attachment, Email, Contact};
+pub use config::{EmailConfig, Timeouts, TransportConfig};
+pub use file::FileTransport;
+pub use mailer::{Mailer, FRONTEND_ROUTES};
+pub use memory::MemoryTransport;
+pub use sendinblue::SendinblueTransport;
+pub use smtp::SmtpTransport;
+pub use template::{Rendered, Templates, DEFAULT_LOCALE};
+pub use transport::EmailTransport;
@@ -0,0 +1,112 @@
+use crate::api_error::ApiError;
+use crate::email::{Contact, Email, EmailTransport, Timeouts};
+use serde::Serialize;
+use reqwest::Url;
+use std::collections::{BTreeMap, HashMap};
+
+#[derive(Serialize)]
+struct SendinblueAttachment<'a> {
+    name: &'a str,
+    content: &'a str,
+}
+
+// The body of a request to the sendinblue smtp api
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct SendinblueEmail<'a> {
+    sender: &'a Contact,
+    to: &'a [Contact],
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    cc: &'a [Contact],
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    bcc: &'a [Contact],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reply_to: Option<&'a Contact>,
+    subject: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    html_content: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    text_content: Option<&'a str>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    headers: &'a BTreeMap<String, String>,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    tags: &'a [String],
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    attachment: Vec<SendinblueAttachment<'a>>,
+}
+
+impl<'a> From<&'a Email> for SendinblueEmail<'a> {
+    fn from(email: &'a Email) -> Self {
+        SendinblueEmail {
+            sender: &email.sender,
+            to: &email.recipients,
+            cc: &email.cc,
+            bcc: &email.bcc,
+            reply_to: email.reply_to.as_ref(),
+            subject: &email.subject,
+            html_content: email.html.as_deref(),
+            text_content: email.text.as_deref(),
+            headers: &email.headers,
+            tags: &email.tags,
+            attachment: email.attachments.iter()
+                .map(|attachment| SendinblueAttachment { name: &attachment.name, content: &attachment.content })
+                .collect(),
+        }
+    }
+}
+
+pub struct SendinblueTransport {
+    api_key: String,
+    api_url: Url,
+    client: reqwest::Client,
+}
+
+impl SendinblueTransport {
+    pub fn new(api_key: String, api_url: Url, timeouts: Timeouts) -> Result<Self, String> {
+        let client = reqwest::Client::builder()
+            .connect_timeout(timeouts.connect)
+            .timeout(timeouts.read)
+            .build()
+            .map_err(|e| format!("Failed to create sendinblue client: {}", e))?;
+
+        Ok(SendinblueTransport { api_key, api_url, client })
+    }
+}
+
+impl EmailTransport for SendinblueTransport {
+    fn send(&self, email: &Email) -> Result<String, ApiError> {
+        // Sendinblue can't reference attachments by content id, so an inline image would arrive as a
+        // regular attachment and the cid: link in the html would be broken
+        if email.attachments.iter().any(|attachment| attachment.content_id.is_some()) {
+            return Err(ApiError::new(422, "Sendinblue can't send inline images, link to hosted images instead"));
+        }
+
+        let url = format!("{}/smtp/email", self.api_url.as_str().trim_end_matches('/'));
+        let mut response = self.client.post(&url)
+            .header("Accept", "application/json")
+            .header("api-key", self.api_key.as_str())
+            .json(&SendinblueEmail::from(email))
+            .send()
+            .map_err(|e| ApiError::new(500, format!("Failed to send email: {}", e)))?;
+
+        let status = response.status().as_u16();
+        let mut body: HashMap<String, String> = response
+            .json()
+            .map_err(|e| ApiError::new(500, format!("Failed to read sendinblue response: {}", e)))?;
+
+        match status {
+            201 => Ok(body.remove("messageId").unwrap_or("".to_string())),
+            _ => {
+                let message = body.remove("message").unwrap_or("Unknown error".to_string());
+                // A bad api key, a timeout or rate limiting may go away, while other client errors
+                // mean the email was rejected and sending it again won't help
+                let status_code = match status {
+                    401 | 403 | 408 | 429 => status,
+                    400..=499 => 422,
+                    _ => 503,
+                };
+                Err(ApiError::new(status_code, format!("Failed to send email: {}", message)))
+            }
+        }
+    }
+}
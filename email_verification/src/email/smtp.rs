@@ -0,0 +1,85 @@
+use crate::api_error::ApiError;
+use crate::email::{mime, Email, EmailTransport, Timeouts};
+use lettre::smtp::authentication::Credentials;
+use lettre::smtp::error::Error as SmtpError;
+use lettre::{ClientSecurity, ClientTlsParameters, SmtpClient, Transport};
+use native_tls::TlsConnector;
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+
+pub struct SmtpTransport {
+    host: String,
+    port: u16,
+    security: ClientSecurity,
+    credentials: Option<Credentials>,
+    timeouts: Timeouts,
+}
+
+impl SmtpTransport {
+    pub fn new(host: &str, port: u16, starttls: bool, credentials: Option<(String, String)>, timeouts: Timeouts) -> Result<Self, String> {
+        let security = match starttls {
+            true => {
+                let connector = TlsConnector::new().map_err(|e| format!("Failed to create tls connector: {}", e))?;
+                ClientSecurity::Required(ClientTlsParameters::new(host.to_string(), connector))
+            },
+            false => ClientSecurity::None,
+        };
+
+        Ok(SmtpTransport {
+            host: host.to_string(),
+            port,
+            security,
+            credentials: credentials.map(|(username, password)| Credentials::new(username, password)),
+            timeouts,
+        })
+    }
+
+    // Lettre connects without a timeout, so the server is reached with one first and lettre
+    // is given the address that answered. The host is resolved for every email to follow DNS
+    // changes, instead of holding on to the address found at startup.
+    fn reachable_address(&self) -> Result<SocketAddr, ApiError> {
+        let addresses = (self.host.as_str(), self.port).to_socket_addrs()
+            .map_err(|e| ApiError::new(503, format!("Failed to resolve smtp server: {}", e)))?;
+
+        let mut error = format!("No address found for smtp server {}", self.host);
+        for address in addresses {
+            match TcpStream::connect_timeout(&address, self.timeouts.connect) {
+                Ok(_) => return Ok(address),
+                Err(e) => error = format!("Failed to connect to smtp server: {}", e),
+            }
+        }
+
+        Err(ApiError::new(503, error))
+    }
+
+    fn client(&self) -> Result<SmtpClient, ApiError> {
+        let mut client = SmtpClient::new(self.reachable_address()?, self.security.clone())
+            .map_err(|e| ApiError::new(503, format!("Failed to create smtp client: {}", e)))?
+            .timeout(Some(self.timeouts.read));
+
+        if let Some(credentials) = &self.credentials {
+            client = client.credentials(credentials.clone());
+        }
+
+        Ok(client)
+    }
+}
+
+impl EmailTransport for SmtpTransport {
+    fn send(&self, email: &Email) -> Result<String, ApiError> {
+        let message = mime::build(email)?;
+        let message_id = message.message_id().to_string();
+
+        self.client()?
+            .transport()
+            .send(message)
+            .map_err(|e| {
+                let status_code = match e {
+                    SmtpError::Permanent(_) => 422,
+                    _ => 503,
+                };
+                ApiError::new(status_code, format!("Failed to send email: {}", e))
+            })?;
+
+        Ok(message_id)
+    }
+}
@@ -0,0 +1,77 @@
+use crate::api_error::ApiError;
+use actix_web::HttpRequest;
+use handlebars::Handlebars;
+use serde::Serialize;
+use std::collections::BTreeSet;
+
+pub const DEFAULT_LOCALE: &str = "en";
+
+pub struct Rendered {
+    pub subject: String,
+    pub html: String,
+    pub text: String,
+}
+
+pub struct Templates {
+    handlebars: Handlebars,
+    locales: BTreeSet<String>,
+}
+
+impl Templates {
+    // Compiles every template in the directory, laid out as <locale>/<name>.<subject|html|txt>.hbs
+    pub fn load(dir: &str) -> Result<Self, String> {
+        let mut handlebars = Handlebars::new();
+        handlebars.set_strict_mode(true);
+        handlebars.register_templates_directory(".hbs", dir)
+            .map_err(|e| format!("Failed to compile email templates in {}: {}", dir, e))?;
+
+        let locales: BTreeSet<String> = handlebars.get_templates()
+            .keys()
+            .filter_map(|name| name.split('/').next())
+            .map(|locale| locale.to_string())
+            .collect();
+
+        if !locales.contains(DEFAULT_LOCALE) {
+            return Err(format!("Email templates for the default locale are missing in {}", dir));
+        }
+
+        Ok(Templates { handlebars, locales })
+    }
+
+    // Falls back to the default locale when the template hasn't been translated
+    pub fn render<T: Serialize>(&self, name: &str, locale: &str, data: &T) -> Result<Rendered, ApiError> {
+        let locale = match self.handlebars.has_template(&format!("{}/{}.subject", locale, name)) {
+            true => locale,
+            false => DEFAULT_LOCALE,
+        };
+
+        let render = |part: &str| {
+            self.handlebars.render(&format!("{}/{}.{}", locale, name, part), data)
+                .map_err(|e| ApiError::new(500, format!("Failed to render email template {}: {}", name, e)))
+        };
+
+        Ok(Rendered {
+            subject: render("subject")?.trim().to_string(),
+            html: render("html")?,
+            text: render("txt")?,
+        })
+    }
+
+    pub fn locales(&self) -> impl Iterator<Item = &str> {
+        self.locales.iter().map(String::as_str)
+    }
+
+    // The first language in Accept-Language that we have templates for
+    pub fn locale(&self, req: &HttpRequest) -> String {
+        req.headers()
+            .get("Accept-Language")
+            .and_then(|header| header.to_str().ok())
+            .unwrap_or("")
+            .split(',')
+            .filter_map(|language| language.split(';').next())
+            .filter_map(|language| language.trim().split('-').next())
+            .map(|language| language.to_lowercase())
+            .find(|language| self.locales.contains(language))
+            .unwrap_or(DEFAULT_LOCALE.to_string())
+    }
+}
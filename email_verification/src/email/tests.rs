@@ -0,0 +1,207 @@
+
+#[cfg(test)]
+mod tests {
+    use crate::email::{Contact, Email, EmailConfig, EmailTransport, Mailer, SendinblueTransport, SmtpTransport, Timeouts, TransportConfig, DEFAULT_LOCALE};
+    use crate::email::mime;
+    use crate::email::sendinblue::SendinblueEmail;
+    use reqwest::Url;
+    use serde_json::json;
+    use std::collections::HashMap;
+    use std::fs;
+    use std::net::TcpListener;
+    use std::time::{Duration, Instant};
+
+    fn config(vars: &[(&str, &str)]) -> Result<EmailConfig, String> {
+        let vars: HashMap<String, String> = vars.iter().map(|(key, value)| (key.to_string(), value.to_string())).collect();
+        EmailConfig::from_vars(|key| vars.get(key).cloned())
+    }
+
+    fn mailer() -> Mailer {
+        let config = config(&[
+            ("EMAIL_SENDER_ADDRESS", "tore@cloudmaker.dev"),
+            ("EMAIL_SENDER_NAME", "Cloudmaker"),
+            ("EMAIL_TRANSPORT", "memory"),
+            ("APP_BASE_URL", "http://127.0.0.1:5000"),
+            ("FRONTEND_URL", "http://127.0.0.1:3000"),
+        ]).unwrap();
+
+        Mailer::new(config).unwrap()
+    }
+
+    // Renders every template in every locale, and writes the results to
+    // target/email-previews so they can be looked at in a browser
+    #[test]
+    fn test_render_previews() {
+        let mailer = mailer();
+        let link = mailer.frontend_link("/register", &[("token", "abc"), ("email", "tore@cloudmaker.dev")]).unwrap();
+        let data = json!({ "link": link });
+        let dir = "target/email-previews";
+        fs::create_dir_all(dir).unwrap();
+
+        for locale in mailer.templates().locales() {
+            for name in &["invite", "change_email", "password_reset", "magic_link"] {
+                let rendered = mailer.templates().render(name, locale, &data)
+                    .unwrap_or_else(|e| panic!("Failed to render {} in {}: {}", name, locale, e));
+
+                assert!(!rendered.subject.is_empty(), "{} in {} has no subject", name, locale);
+                assert!(rendered.html.contains("token=abc&amp;email=tore%40cloudmaker.dev"), "{} in {} is missing the link in html", name, locale);
+                assert!(rendered.text.contains(&link), "{} in {} is missing the link in text", name, locale);
+
+                fs::write(format!("{}/{}.{}.html", dir, name, locale), &rendered.html).unwrap();
+                fs::write(format!("{}/{}.{}.txt", dir, name, locale), &rendered.text).unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn test_render_falls_back_to_default_locale() {
+        let data = json!({ "link": "http://127.0.0.1:3000/register" });
+        let mailer = mailer();
+        let fallback = mailer.templates().render("invite", "xx", &data).unwrap();
+        let default = mailer.templates().render("invite", DEFAULT_LOCALE, &data).unwrap();
+
+        assert_eq!(default.subject, fallback.subject);
+    }
+
+    #[test]
+    fn test_config_validation() {
+        let required = [
+            ("EMAIL_SENDER_ADDRESS", "tore@cloudmaker.dev"),
+            ("APP_BASE_URL", "http://127.0.0.1:5000"),
+            ("FRONTEND_URL", "http://127.0.0.1:3000"),
+        ];
+
+        let err = config(&required).unwrap_err();
+        assert!(err.contains("SENDINBLUE_API_KEY"), "Missing api key should fail fast, got: {}", err);
+
+        let err = config(&[required[0], required[1], ("SENDINBLUE_API_KEY", "key")]).unwrap_err();
+        assert!(err.contains("FRONTEND_URL"), "Missing frontend url should fail fast, got: {}", err);
+
+        let sendinblue = config(&[required[0], required[1], required[2], ("SENDINBLUE_API_KEY", "key")]).unwrap();
+        match sendinblue.transport {
+            TransportConfig::Sendinblue { api_url, .. } => assert_eq!(api_url.as_str(), "https://api.sendinblue.com/v3"),
+            _ => panic!("Expected the sendinblue transport by default"),
+        }
+
+        let err = config(&[("EMAIL_SENDER_ADDRESS", "not an email"), required[1]]).unwrap_err();
+        assert!(err.contains("not a valid email address"));
+
+        let err = config(&[required[0], ("APP_BASE_URL", "ftp://cloudmaker.dev")]).unwrap_err();
+        assert!(err.contains("not a valid http url"));
+
+        let err = config(&[required[0], required[1], ("EMAIL_TRANSPORT", "smtp"), ("SMTP_HOST", "localhost"), ("SMTP_USERNAME", "tore")]).unwrap_err();
+        assert!(err.contains("must be set together"));
+    }
+
+    #[test]
+    fn test_compose_uses_configured_sender() {
+        let email = mailer().compose("invite", DEFAULT_LOCALE, &json!({ "link": "http://127.0.0.1:5000/register" })).unwrap();
+        let body = serde_json::to_value(SendinblueEmail::from(&email)).unwrap();
+
+        assert_eq!(body["sender"], json!({ "email": "tore@cloudmaker.dev", "name": "Cloudmaker" }));
+    }
+
+    fn full_email() -> Email {
+        Email::new(Contact::new("tore@cloudmaker.dev", "Cloudmaker"))
+            .add_recipient("to@cloudmaker.dev")
+            .add_cc("cc@cloudmaker.dev")
+            .add_bcc("bcc@cloudmaker.dev")
+            .set_reply_to("support@cloudmaker.dev")
+            .add_header("X-Campaign", "welcome")
+            .add_tag("invite")
+            .set_subject("Welcome")
+            .set_html("<img src=\"cid:logo\">")
+            .set_text("Welcome")
+            .add_inline_image("logo", "logo.png", "image/png", &[1, 2, 3])
+            .add_attachment("terms.txt", "text/plain", b"Terms")
+    }
+
+    #[test]
+    fn test_sendinblue_serialization() {
+        let email = full_email();
+        let body = serde_json::to_value(SendinblueEmail::from(&email)).unwrap();
+
+        assert_eq!(body["cc"], json!([{ "email": "cc@cloudmaker.dev" }]));
+        assert_eq!(body["bcc"], json!([{ "email": "bcc@cloudmaker.dev" }]));
+        assert_eq!(body["replyTo"], json!({ "email": "support@cloudmaker.dev" }));
+        assert_eq!(body["headers"], json!({ "X-Campaign": "welcome" }));
+        assert_eq!(body["tags"], json!(["invite"]));
+        assert_eq!(body["textContent"], json!("Welcome"));
+        assert_eq!(body["attachment"], json!([
+            { "name": "logo.png", "content": base64::encode(&[1, 2, 3]) },
+            { "name": "terms.txt", "content": base64::encode(b"Terms") },
+        ]));
+    }
+
+    #[test]
+    fn test_mime_message() {
+        let message = mime::build(&full_email()).unwrap();
+
+        let recipients: Vec<String> = message.envelope().to().iter().map(|address| address.to_string()).collect();
+        assert!(recipients.contains(&"bcc@cloudmaker.dev".to_string()), "Bcc should be in the envelope");
+
+        let message = message.message_to_string().unwrap();
+        assert!(message.contains("Cc: <cc@cloudmaker.dev>"));
+        assert!(message.contains("Reply-To:"));
+        assert!(!message.contains("bcc@cloudmaker.dev"), "Bcc should not be in the headers");
+        assert!(message.contains("X-Campaign: welcome"));
+        assert!(message.contains("X-Tags: invite"));
+        assert!(message.contains("multipart/related"));
+        assert!(message.contains("multipart/alternative"));
+        assert!(message.contains("Content-ID: <logo>"));
+        assert!(message.contains("attachment; filename=\"terms.txt\""));
+        assert!(message.contains(&base64::encode(b"Terms")));
+    }
+
+    #[test]
+    fn test_attachment_filenames_are_escaped() {
+        let email = Email::new(Contact::new("tore@cloudmaker.dev", "Cloudmaker"))
+            .add_recipient("to@cloudmaker.dev")
+            .set_text("Welcome")
+            .add_attachment("\"terms\".txt\r\nX-Injected: yes", "text/plain", b"Terms")
+            .add_attachment("vilkår.txt", "text/plain", b"Terms");
+
+        let message = mime::build(&email).unwrap().message_to_string().unwrap();
+        assert!(!message.contains("\r\nX-Injected"), "The filename should not be able to add headers");
+        assert!(message.contains("filename=\"_terms_.txt__X-Injected: yes\"; filename*=utf-8''%22terms%22.txt%0D%0AX-Injected%3A%20yes"));
+        assert!(message.contains("filename=\"vilk_r.txt\"; filename*=utf-8''vilk%C3%A5r.txt"));
+    }
+
+    #[test]
+    fn test_sendinblue_refuses_inline_images() {
+        let timeouts = Timeouts { connect: Duration::from_secs(1), read: Duration::from_secs(1) };
+        let transport = SendinblueTransport::new("key".to_string(), Url::parse("http://127.0.0.1:9").unwrap(), timeouts).unwrap();
+
+        let err = transport.send(&full_email()).err().expect("Inline images should be refused");
+        assert_eq!(422, err.status_code, "Inline images should never be retried");
+    }
+
+    #[test]
+    fn test_smtp_resolves_host_when_sending() {
+        let timeouts = Timeouts { connect: Duration::from_secs(1), read: Duration::from_secs(1) };
+        let transport = SmtpTransport::new("smtp.invalid", 587, false, None, timeouts);
+        assert!(transport.is_ok(), "The smtp host should not be resolved at startup");
+
+        // A server that takes the connection but never answers
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let transport = SmtpTransport::new("127.0.0.1", port, false, None, timeouts).unwrap();
+
+        let started = Instant::now();
+        let err = transport.send(&full_email()).err().expect("Sending to a silent server should fail");
+        assert_eq!(503, err.status_code, "A timeout should be retried");
+        assert!(started.elapsed() < Duration::from_secs(10), "Sending should give up after the read timeout");
+    }
+
+    #[test]
+    fn test_smtp_connect_times_out() {
+        let timeouts = Timeouts { connect: Duration::from_secs(1), read: Duration::from_secs(30) };
+        // Not routable, so connecting hangs until the timeout unless the network refuses it right away
+        let transport = SmtpTransport::new("10.255.255.1", 587, false, None, timeouts).unwrap();
+
+        let started = Instant::now();
+        let err = transport.send(&full_email()).err().expect("Sending to an unreachable server should fail");
+        assert_eq!(503, err.status_code, "An unreachable server should be retried");
+        assert!(started.elapsed() < Duration::from_secs(10), "Connecting should give up after the connect timeout");
+    }
+}
@@ -0,0 +1,8 @@
+use crate::api_error::ApiError;
+use crate::email::Email;
+
+pub trait EmailTransport: Send + Sync {
+    // Returns the message id. Errors with a status below 500 mean the email was
+    // rejected for good, anything else may work if retried.
+    fn send(&self, email: &Email) -> Result<String, ApiError>;
+}
@@ -0,0 +1,77 @@
+use crate::api_error::ApiError;
+use crate::email_verification_token::derive_secret;
+use crate::schema::email_change_token;
+use chrono::{NaiveDateTime, Utc, Duration};
+use diesel::prelude::*;
+use rand::Rng;
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+// Confirms that a signed in user owns the email they want to change to. A user has at most one
+// pending change, and several users may ask for the same address, since only one can confirm it.
+#[derive(Queryable, Insertable)]
+#[table_name = "email_change_token"]
+pub struct EmailChangeToken {
+    pub id: Vec<u8>,
+    pub user_id: Uuid,
+    pub email: String,
+    pub expires_at: NaiveDateTime,
+    pub created_at: NaiveDateTime,
+    pub salt: Vec<u8>,
+}
+
+impl EmailChangeToken {
+    pub fn find_unexpired(conn: &PgConnection, user_id: Uuid) -> Result<Self, ApiError> {
+        let token = email_change_token::table
+            .filter(email_change_token::user_id.eq(user_id))
+            .filter(email_change_token::expires_at.gt(Utc::now().naive_utc()))
+            .first(conn)?;
+
+        Ok(token)
+    }
+
+    // Only the hash of the token is stored, the returned secret is what gets sent to the user.
+    pub fn create(conn: &PgConnection, user_id: Uuid, email: String) -> Result<(Self, Vec<u8>), ApiError> {
+        let salt = rand::thread_rng().gen::<[u8; 32]>().to_vec();
+        let secret = derive_secret(&salt);
+        let id = Sha256::digest(&secret).to_vec();
+        let created_at = Utc::now().naive_utc();
+        let expires_at = created_at + Duration::hours(12);
+        let token = EmailChangeToken { id, user_id, email, expires_at, created_at, salt };
+
+        let token = diesel::insert_into(email_change_token::table)
+            .values(&token)
+            .on_conflict(email_change_token::user_id)
+            .do_update()
+            .set((
+                email_change_token::id.eq(&token.id),
+                email_change_token::email.eq(&token.email),
+                email_change_token::salt.eq(&token.salt),
+                email_change_token::created_at.eq(&token.created_at),
+                email_change_token::expires_at.eq(&token.expires_at),
+            ))
+            .get_result(conn)?;
+
+        Ok((token, secret))
+    }
+
+    pub fn secret(&self) -> Vec<u8> {
+        derive_secret(&self.salt)
+    }
+
+    // Deletes the token as it's read, so it can only be used once. Pass the connection of the
+    // transaction that changes the email, so the token survives if that fails.
+    pub fn redeem(conn: &PgConnection, secret: &[u8], user_id: Uuid) -> Result<Self, ApiError> {
+        let id = Sha256::digest(secret).to_vec();
+
+        let token = diesel::delete(
+                email_change_token::table
+                    .filter(email_change_token::id.eq(&id))
+                    .filter(email_change_token::user_id.eq(user_id))
+                    .filter(email_change_token::expires_at.gt(Utc::now().naive_utc()))
+            )
+            .get_result(conn)?;
+
+        Ok(token)
+    }
+}
@@ -0,0 +1,28 @@
+use sha2::{Digest, Sha256};
+
+// Sendinblue authenticates webhooks with the credentials set up along with the webhook, either a
+// bearer token or basic auth. The shared secret is the token, or the password for basic auth.
+pub fn is_authorized(secret: &str, authorization: &str) -> bool {
+    let mut parts = authorization.trim().splitn(2, ' ');
+    let scheme = parts.next().unwrap_or("");
+    let credentials = parts.next().unwrap_or("").trim();
+
+    let provided = if scheme.eq_ignore_ascii_case("bearer") {
+        credentials.to_string()
+    } else if scheme.eq_ignore_ascii_case("basic") {
+        let decoded = match base64::decode(credentials).ok().and_then(|decoded| String::from_utf8(decoded).ok()) {
+            Some(decoded) => decoded,
+            None => return false,
+        };
+
+        match decoded.splitn(2, ':').nth(1) {
+            Some(password) => password.to_string(),
+            None => return false,
+        }
+    } else {
+        return false;
+    };
+
+    // Comparing the hashes keeps the time it takes from telling how much of the secret matched
+    Sha256::digest(provided.as_bytes()) == Sha256::digest(secret.as_bytes())
+}
@@ -0,0 +1,8 @@
+mod authorization;
+mod model;
+mod routes;
+mod tests;
+
+pub use model::{EmailEvent, EmailEventMessage};
+pub use routes::init_routes;
+pub use authorization::is_authorized;
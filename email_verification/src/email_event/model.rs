@@ -0,0 +1,62 @@
+use crate::api_error::ApiError;
+use crate::db;
+use crate::email_suppression::EmailSuppression;
+use crate::schema::email_event;
+use chrono::NaiveDateTime;
+use diesel::prelude::*;
+use serde::Serialize;
+use uuid::Uuid;
+
+#[derive(Insertable)]
+#[table_name = "email_event"]
+pub struct EmailEventMessage {
+    pub message_id: String,
+    pub email: String,
+    pub event: String,
+    pub reason: Option<String>,
+    pub occurred_at: NaiveDateTime,
+}
+
+// A delivery event reported by the email provider for a message we sent
+#[derive(Serialize, Queryable)]
+pub struct EmailEvent {
+    pub id: Uuid,
+    pub message_id: String,
+    pub email: String,
+    pub event: String,
+    pub reason: Option<String>,
+    pub occurred_at: NaiveDateTime,
+    pub created_at: NaiveDateTime,
+}
+
+impl EmailEvent {
+    pub fn find_by_message_id(message_id: &str) -> Result<Vec<Self>, ApiError> {
+        let conn = db::connection()?;
+
+        let events = email_event::table
+            .filter(email_event::message_id.eq(message_id))
+            .order(email_event::occurred_at.asc())
+            .load(&conn)?;
+
+        Ok(events)
+    }
+
+    // Providers retry webhooks, so an event we already have is ignored.
+    // Hard bounces and complaints put the address on the suppression list.
+    pub fn record(event: EmailEventMessage) -> Result<(), ApiError> {
+        let conn = db::connection()?;
+
+        conn.transaction(|| {
+            diesel::insert_into(email_event::table)
+                .values(&event)
+                .on_conflict_do_nothing()
+                .execute(&conn)?;
+
+            if event.event == "hard_bounce" || event.event == "complaint" {
+                EmailSuppression::add(&conn, &event.email, &event.event, Some(event.message_id.clone()))?;
+            }
+
+            Ok(())
+        })
+    }
+}
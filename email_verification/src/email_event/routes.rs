@@ -0,0 +1,87 @@
+use crate::api_error::ApiError;
+use crate::auth::Authenticated;
+use crate::email::Mailer;
+use crate::email_event::{self, EmailEvent, EmailEventMessage};
+use actix_web::{get, post, web, http::header, HttpRequest, HttpResponse};
+use chrono::{NaiveDateTime, Utc};
+use serde::Deserialize;
+use serde_json::json;
+
+// The event as the provider posts it
+#[derive(Deserialize)]
+struct DeliveryEventMessage {
+    event: String,
+    email: String,
+    #[serde(rename = "message-id")]
+    message_id: String,
+    reason: Option<String>,
+    ts_event: Option<i64>,
+}
+
+// Maps the provider's event names to ours, and leaves out the ones we don't track
+fn event_name(event: &str) -> Option<&'static str> {
+    match event {
+        "delivered" => Some("delivered"),
+        "soft_bounce" | "deferred" => Some("soft_bounce"),
+        "hard_bounce" | "invalid_email" | "blocked" => Some("hard_bounce"),
+        "spam" | "complaint" => Some("complaint"),
+        _ => None,
+    }
+}
+
+#[post("/webhooks/email")]
+async fn delivery_event(body: web::Bytes, mailer: web::Data<Mailer>, req: HttpRequest) -> Result<HttpResponse, ApiError> {
+    let secret = mailer.config.webhook_secret.as_ref()
+        .ok_or(ApiError::new(404, "Not found"))?;
+
+    let authorization = req.headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|authorization| authorization.to_str().ok())
+        .unwrap_or("");
+
+    if !email_event::is_authorized(secret, authorization) {
+        return Err(ApiError::new(401, "Unauthorized"));
+    }
+
+    let message: DeliveryEventMessage = serde_json::from_slice(&body)
+        .map_err(|e| ApiError::new(400, format!("Invalid event: {}", e)))?;
+
+    let event = match event_name(&message.event) {
+        Some(event) => event,
+        None => return Ok(HttpResponse::Ok().json(json!({ "message": "Event ignored" }))),
+    };
+
+    let occurred_at = message.ts_event
+        .and_then(|ts| NaiveDateTime::from_timestamp_opt(ts, 0))
+        .unwrap_or(Utc::now().naive_utc());
+
+    let event = EmailEventMessage {
+        message_id: message.message_id,
+        email: message.email,
+        event: event.to_string(),
+        reason: message.reason,
+        occurred_at,
+    };
+
+    EmailEvent::record(event)?;
+
+    Ok(HttpResponse::Ok().json(json!({ "message": "Event recorded" })))
+}
+
+#[derive(Deserialize)]
+struct EventsQuery {
+    message_id: String,
+}
+
+#[get("/admin/email-events")]
+async fn find_by_message_id(query: web::Query<EventsQuery>, auth: Authenticated) -> Result<HttpResponse, ApiError> {
+    auth.require_admin()?;
+
+    let events = EmailEvent::find_by_message_id(&query.message_id)?;
+    Ok(HttpResponse::Ok().json(events))
+}
+
+pub fn init_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(delivery_event);
+    cfg.service(find_by_message_id);
+}
@@ -0,0 +1,100 @@
+
+#[cfg(test)]
+mod tests {
+    use crate::auth;
+    use crate::cache;
+    use crate::db;
+    use crate::email::{EmailConfig, Mailer};
+    use crate::email_event::{self, init_routes, EmailEvent};
+    use crate::email_suppression::EmailSuppression;
+    use crate::email_verification_token::EmailVerificationToken;
+    use actix_redis::RedisSession;
+    use actix_web::{test::{self, TestRequest}, web, App};
+    use redis::Commands;
+    use serde_json::json;
+    use std::env;
+    use uuid::Uuid;
+
+    const SECRET: &str = "webhook-secret";
+
+    fn mailer() -> web::Data<Mailer> {
+        crate::test::init();
+        let mut config = EmailConfig::from_env().expect("Invalid email config");
+        config.webhook_secret = Some(SECRET.to_string());
+        web::Data::new(Mailer::new(config).expect("Failed to initialize email"))
+    }
+
+    #[test]
+    fn test_authorization() {
+        let basic = base64::encode(format!("sendinblue:{}", SECRET));
+
+        assert!(email_event::is_authorized(SECRET, &format!("Bearer {}", SECRET)));
+        assert!(email_event::is_authorized(SECRET, &format!("Basic {}", basic)));
+        assert!(!email_event::is_authorized(SECRET, "Bearer other-secret"), "Another token should fail");
+        assert!(!email_event::is_authorized(SECRET, &format!("Basic {}", base64::encode("sendinblue:other-secret"))), "Another password should fail");
+        assert!(!email_event::is_authorized(SECRET, &format!("Basic {}", base64::encode(SECRET))), "Basic auth without a password should fail");
+        assert!(!email_event::is_authorized(SECRET, SECRET), "The secret without a scheme should fail");
+        assert!(!email_event::is_authorized(SECRET, ""));
+    }
+
+    #[actix_rt::test]
+    async fn test_hard_bounce_suppresses_invite() {
+        let mailer = mailer();
+        let email = format!("{}@cloudmaker.dev", Uuid::new_v4());
+        let message_id = format!("<{}@smtp-relay.sendinblue.com>", Uuid::new_v4());
+
+        let redis_port = env::var("REDIS_PORT").expect("Redis port not set");
+        let redis_host = env::var("REDIS_HOST").expect("Redis host not set");
+
+        let mut app = test::init_service(
+            App::new()
+                .app_data(mailer.clone())
+                .wrap(RedisSession::new(format!("{}:{}", redis_host, redis_port), &[0; 32]))
+                .configure(init_routes)
+                .configure(auth::init_routes)
+        ).await;
+
+        let body = serde_json::to_vec(&json!({
+            "event": "hard_bounce",
+            "email": email,
+            "message-id": message_id,
+            "reason": "unknown user",
+            "ts_event": 1760000000,
+        })).unwrap();
+
+        let resp = TestRequest::post().uri("/webhooks/email")
+            .header("Content-Type", "application/json")
+            .set_payload(body.clone())
+            .send_request(&mut app).await;
+        assert_eq!(401, resp.status().as_u16(), "Webhook without credentials should be rejected");
+
+        for _ in 0..2 {
+            let resp = TestRequest::post().uri("/webhooks/email")
+                .header("Content-Type", "application/json")
+                .header("Authorization", format!("Bearer {}", SECRET))
+                .set_payload(body.clone())
+                .send_request(&mut app).await;
+            assert!(resp.status().is_success(), "Failed to record event");
+        }
+
+        let events = EmailEvent::find_by_message_id(&message_id).unwrap();
+        assert_eq!(1, events.len(), "A retried webhook should only be recorded once");
+        assert_eq!("hard_bounce", events[0].event);
+        assert!(EmailSuppression::is_suppressed(&email.to_uppercase()).unwrap());
+
+        let resp = TestRequest::post().uri("/invite")
+            .set_json(&json!({ "email": email }))
+            .send_request(&mut app).await;
+        assert!(resp.status().is_success(), "Invite to a suppressed address should look like any other");
+        let conn = db::connection().unwrap();
+        let invited = EmailVerificationToken::find_unexpired(&conn, &email);
+        assert_eq!(404, invited.err().map(|e| e.status_code).unwrap_or(200), "Suppressed address should not be invited");
+
+        let mut cache = cache::connection().unwrap();
+        let _: () = cache.del(format!("throttle.invite.email.{}", email)).unwrap();
+        let _: () = cache.del(format!("throttle.invite.daily.{}", email)).unwrap();
+
+        EmailSuppression::delete(&email).unwrap();
+        assert!(!EmailSuppression::is_suppressed(&email).unwrap());
+    }
+}
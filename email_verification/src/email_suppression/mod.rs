@@ -0,0 +1,5 @@
+mod model;
+mod routes;
+
+pub use model::EmailSuppression;
+pub use routes::init_routes;
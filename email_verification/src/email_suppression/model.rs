@@ -0,0 +1,68 @@
+use crate::api_error::ApiError;
+use crate::db;
+use crate::schema::email_suppression;
+use chrono::{NaiveDateTime, Utc};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+
+// Addresses we no longer send to, because they bounced for good or the recipient marked us as spam
+#[derive(Serialize, Deserialize, Queryable, Insertable)]
+#[table_name = "email_suppression"]
+pub struct EmailSuppression {
+    pub email: String,
+    pub reason: String,
+    pub message_id: Option<String>,
+    pub created_at: NaiveDateTime,
+}
+
+impl EmailSuppression {
+    pub fn find_all() -> Result<Vec<Self>, ApiError> {
+        let conn = db::connection()?;
+
+        let suppressions = email_suppression::table
+            .order(email_suppression::created_at.desc())
+            .load(&conn)?;
+
+        Ok(suppressions)
+    }
+
+    pub fn is_suppressed(email: &str) -> Result<bool, ApiError> {
+        let conn = db::connection()?;
+
+        let suppressed = diesel::select(diesel::dsl::exists(
+                email_suppression::table.filter(email_suppression::email.eq(email.to_lowercase()))
+            ))
+            .get_result(&conn)?;
+
+        Ok(suppressed)
+    }
+
+    // Keeps the first reason if the address is already suppressed
+    pub fn add(conn: &PgConnection, email: &str, reason: &str, message_id: Option<String>) -> Result<(), ApiError> {
+        let suppression = EmailSuppression {
+            email: email.to_lowercase(),
+            reason: reason.to_string(),
+            message_id,
+            created_at: Utc::now().naive_utc(),
+        };
+
+        diesel::insert_into(email_suppression::table)
+            .values(suppression)
+            .on_conflict_do_nothing()
+            .execute(conn)?;
+
+        Ok(())
+    }
+
+    pub fn delete(email: &str) -> Result<usize, ApiError> {
+        let conn = db::connection()?;
+
+        let res = diesel::delete(
+                email_suppression::table
+                    .filter(email_suppression::email.eq(email.to_lowercase()))
+            )
+            .execute(&conn)?;
+
+        Ok(res)
+    }
+}
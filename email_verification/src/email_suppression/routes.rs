@@ -0,0 +1,26 @@
+use crate::api_error::ApiError;
+use crate::auth::Authenticated;
+use crate::email_suppression::EmailSuppression;
+use actix_web::{delete, get, web, HttpResponse};
+use serde_json::json;
+
+#[get("/admin/email-suppressions")]
+async fn find_all(auth: Authenticated) -> Result<HttpResponse, ApiError> {
+    auth.require_admin()?;
+
+    let suppressions = EmailSuppression::find_all()?;
+    Ok(HttpResponse::Ok().json(suppressions))
+}
+
+#[delete("/admin/email-suppressions/{email}")]
+async fn delete(email: web::Path<String>, auth: Authenticated) -> Result<HttpResponse, ApiError> {
+    auth.require_admin()?;
+
+    let num_deleted = EmailSuppression::delete(&email.into_inner())?;
+    Ok(HttpResponse::Ok().json(json!({ "deleted": num_deleted })))
+}
+
+pub fn init_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(find_all);
+    cfg.service(delete);
+}
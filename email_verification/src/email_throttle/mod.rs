@@ -0,0 +1,3 @@
+mod throttle;
+
+pub use throttle::{check, init, too_many_requests};
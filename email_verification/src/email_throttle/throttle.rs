@@ -0,0 +1,137 @@
+use crate::api_error::ApiError;
+use crate::cache;
+use actix_web::{HttpRequest, HttpResponse};
+use lazy_static::lazy_static;
+use redis::Commands;
+use serde_json::json;
+use std::env;
+use std::net::IpAddr;
+
+const DAY: usize = 24 * 60 * 60;
+
+lazy_static! {
+    static ref THROTTLE_CONFIG: ThrottleConfig = ThrottleConfig::from_env();
+}
+
+pub fn init() {
+    info!("Initializing email throttling");
+    lazy_static::initialize(&THROTTLE_CONFIG);
+}
+
+// Limits how often requests can make us send email, so the endpoints can't be used to spam an address
+struct ThrottleConfig {
+    // Seconds between emails to the same address
+    email_cooldown: usize,
+    // Seconds between emails requested from the same IP
+    ip_cooldown: usize,
+    // Emails to the same address within 24 hours
+    daily_limit: usize,
+    // Proxies that are trusted to tell the client address in X-Forwarded-For
+    trusted_proxies: Vec<IpAddr>,
+}
+
+impl ThrottleConfig {
+    fn from_env() -> Self {
+        ThrottleConfig {
+            email_cooldown: env_usize("EMAIL_COOLDOWN", 60),
+            ip_cooldown: env_usize("EMAIL_IP_COOLDOWN", 10),
+            daily_limit: env_usize("EMAIL_DAILY_LIMIT", 5),
+            trusted_proxies: env::var("TRUSTED_PROXIES")
+                .unwrap_or_default()
+                .split(',')
+                .map(|ip| ip.trim())
+                .filter(|ip| !ip.is_empty())
+                .map(|ip| ip.parse().unwrap_or_else(|_| panic!("TRUSTED_PROXIES has an invalid address: {}", ip)))
+                .collect(),
+        }
+    }
+}
+
+// Records an email of the given kind to the address, unless a limit is reached.
+// Returns how many seconds to wait before trying again when throttled.
+pub fn check(kind: &str, email: &str, req: &HttpRequest) -> Result<Option<usize>, ApiError> {
+    let config = &*THROTTLE_CONFIG;
+    let mut cache = cache::connection()?;
+    let email = email.to_lowercase();
+
+    if let Some(ip) = client_ip(req, &config.trusted_proxies) {
+        let key = format!("throttle.{}.ip.{}", kind, ip);
+        if let Some(retry_after) = cooldown(&mut cache, &key, config.ip_cooldown)? {
+            return Ok(Some(retry_after));
+        }
+    }
+
+    let key = format!("throttle.{}.email.{}", kind, email);
+    if let Some(retry_after) = cooldown(&mut cache, &key, config.email_cooldown)? {
+        return Ok(Some(retry_after));
+    }
+
+    // Counting first keeps concurrent requests from all getting in under the limit.
+    // The 24 hours start with the first email.
+    let daily_key = format!("throttle.{}.daily.{}", kind, email);
+    let count: usize = cache.incr(&daily_key, 1)?;
+    if count == 1 {
+        let _: () = cache.expire(&daily_key, DAY)?;
+    }
+
+    if count > config.daily_limit {
+        return Ok(Some(ttl(&mut cache, &daily_key, DAY)?));
+    }
+
+    Ok(None)
+}
+
+pub fn too_many_requests(retry_after: usize) -> HttpResponse {
+    HttpResponse::TooManyRequests()
+        .header("Retry-After", retry_after.to_string())
+        .json(json!({ "message": "Too many requests, try again later" }))
+}
+
+// Starts the cooldown unless it's already running, in which case the time left is returned
+fn cooldown(cache: &mut cache::CacheConnection, key: &str, seconds: usize) -> Result<Option<usize>, ApiError> {
+    if seconds == 0 {
+        return Ok(None);
+    }
+
+    let started: Option<String> = redis::cmd("SET").arg(key).arg(1).arg("NX").arg("EX").arg(seconds).query(&mut **cache)?;
+
+    match started {
+        Some(_) => Ok(None),
+        None => Ok(Some(ttl(cache, key, seconds)?)),
+    }
+}
+
+fn ttl(cache: &mut cache::CacheConnection, key: &str, default: usize) -> Result<usize, ApiError> {
+    let ttl: i64 = cache.ttl(key)?;
+    Ok(if ttl > 0 { ttl as usize } else { default })
+}
+
+// The peer address is used, unless it's a trusted proxy. Then the client is the last address in
+// X-Forwarded-For that wasn't added by a trusted proxy, as anything before it can be made up.
+fn client_ip(req: &HttpRequest, trusted_proxies: &[IpAddr]) -> Option<IpAddr> {
+    let peer = req.peer_addr()?.ip();
+
+    if !trusted_proxies.contains(&peer) {
+        return Some(peer);
+    }
+
+    let forwarded_for = req.headers()
+        .get_all("X-Forwarded-For")
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(|ip| ip.trim().parse::<IpAddr>().ok())
+        .collect::<Vec<_>>();
+
+    let client = forwarded_for.into_iter()
+        .rev()
+        .find(|ip| !trusted_proxies.contains(ip));
+
+    Some(client.unwrap_or(peer))
+}
+
+fn env_usize(key: &str, default: usize) -> usize {
+    match env::var(key) {
+        Ok(value) => value.parse().unwrap_or_else(|_| panic!("{} must be a positive number", key)),
+        Err(_) => default,
+    }
+}
@@ -0,0 +1,23 @@
+use crate::email_verification_token::EmailVerificationToken;
+use actix_web::web;
+use std::env;
+use std::time::Duration;
+
+// Deletes expired tokens every EMAIL_TOKEN_CLEANUP_INTERVAL seconds. Has to be called from within the actix runtime.
+pub fn start() {
+    let interval = env::var("EMAIL_TOKEN_CLEANUP_INTERVAL")
+        .map(|interval| interval.parse().expect("Email token cleanup interval must be a number of seconds"))
+        .unwrap_or(3600);
+
+    actix_rt::spawn(async move {
+        let mut interval = actix_rt::time::interval(Duration::from_secs(interval));
+        loop {
+            interval.tick().await;
+            match web::block(EmailVerificationToken::delete_expired).await {
+                Ok(0) => (),
+                Ok(deleted) => info!("Deleted {} expired email verification tokens", deleted),
+                Err(e) => error!("Failed to delete expired email verification tokens: {}", e),
+            }
+        }
+    });
+}
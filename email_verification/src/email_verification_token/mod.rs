@@ -1,6 +1,10 @@
+mod cleanup;
 mod model;
 
+pub use cleanup::start as start_cleanup;
 pub use model::{
+    derive_secret,
+    init,
     EmailVerificationToken,
     EmailVerificationTokenMessage,
 };
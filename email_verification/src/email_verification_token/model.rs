@@ -3,8 +3,26 @@ use crate::db;
 use crate::schema::email_verification_token;
 use chrono::{NaiveDateTime, Utc, Duration};
 use diesel::prelude::*;
+use hmac::{Hmac, Mac};
+use lazy_static::lazy_static;
 use rand::Rng;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::env;
+
+lazy_static! {
+    static ref TOKEN_KEY: Vec<u8> = {
+        let key = env::var("EMAIL_TOKEN_KEY").expect("Email token key not set");
+        let key = hex::decode(key).expect("Email token key must be hex encoded");
+        assert_eq!(32, key.len(), "Email token key must be 32 bytes");
+        key
+    };
+}
+
+pub fn init() {
+    info!("Initializing email verification tokens");
+    lazy_static::initialize(&TOKEN_KEY);
+}
 
 #[derive(Deserialize, Clone)]
 pub struct EmailVerificationTokenMessage {
@@ -19,27 +37,30 @@ pub struct EmailVerificationToken {
     pub email: String,
     pub expires_at: NaiveDateTime,
     pub created_at: NaiveDateTime,
+    // The secret is derived from the salt with a key that isn't stored in the database,
+    // so the token can be sent again without storing the secret itself
+    #[serde(skip_serializing)]
+    pub salt: Option<Vec<u8>>,
 }
 
 impl EmailVerificationToken {
-    pub fn find(id: &Vec<u8>) -> Result<Self, ApiError> {
-        let conn = db::connection()?;
-
+    pub fn find_unexpired(conn: &PgConnection, email: &str) -> Result<Self, ApiError> {
         let token = email_verification_token::table
-            .filter(email_verification_token::id.eq(id))
-            .first(&conn)?;
+            .filter(email_verification_token::email.eq(email))
+            .filter(email_verification_token::expires_at.gt(Utc::now().naive_utc()))
+            .first(conn)?;
 
         Ok(token)
     }
 
-    pub fn create(body: EmailVerificationTokenMessage) -> Result<Self, ApiError> {
-        let conn = db::connection()?;
-
-        let id = rand::thread_rng().gen::<[u8; 32]>().to_vec();
-        let email = body.email;
+    // Only the hash of the token is stored, the returned secret is what gets sent to the user.
+    pub fn create(conn: &PgConnection, body: EmailVerificationTokenMessage) -> Result<(Self, Vec<u8>), ApiError> {
+        let salt = rand::thread_rng().gen::<[u8; 32]>().to_vec();
+        let secret = derive_secret(&salt);
+        let id = Sha256::digest(&secret).to_vec();
         let created_at = Utc::now().naive_utc();
         let expires_at = created_at + Duration::hours(12);
-        let token = EmailVerificationToken { id, email, expires_at, created_at };
+        let token = EmailVerificationToken { id, email: body.email, expires_at, created_at, salt: Some(salt) };
 
         let token = diesel::insert_into(email_verification_token::table)
             .values(&token)
@@ -49,8 +70,30 @@ impl EmailVerificationToken {
                 email_verification_token::id.eq(&token.id),
                 email_verification_token::created_at.eq(&token.created_at),
                 email_verification_token::expires_at.eq(&token.expires_at),
+                email_verification_token::salt.eq(&token.salt),
             ))
-            .get_result(&conn)?;
+            .get_result(conn)?;
+
+        Ok((token, secret))
+    }
+
+    // Tokens from before the salt was added can't be sent again
+    pub fn secret(&self) -> Option<Vec<u8>> {
+        self.salt.as_ref().map(|salt| derive_secret(salt))
+    }
+
+    // Deletes the invitation as it's read, so it can only be used once. Pass the connection of the
+    // transaction that acts on the token, so the token survives if that fails.
+    pub fn redeem(conn: &PgConnection, secret: &[u8], email: &str) -> Result<Self, ApiError> {
+        let id = Sha256::digest(secret).to_vec();
+
+        let token = diesel::delete(
+                email_verification_token::table
+                    .filter(email_verification_token::id.eq(&id))
+                    .filter(email_verification_token::email.eq(email))
+                    .filter(email_verification_token::expires_at.gt(Utc::now().naive_utc()))
+            )
+            .get_result(conn)?;
 
         Ok(token)
     }
@@ -66,4 +109,24 @@ impl EmailVerificationToken {
 
         Ok(res)
     }
-}
\ No newline at end of file
+
+    pub fn delete_expired() -> Result<usize, ApiError> {
+        let conn = db::connection()?;
+
+        let res = diesel::delete(
+                email_verification_token::table
+                    .filter(email_verification_token::expires_at.le(Utc::now().naive_utc()))
+            )
+            .execute(&conn)?;
+
+        Ok(res)
+    }
+}
+
+// Also used for the email change tokens
+pub fn derive_secret(salt: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_varkey(&TOKEN_KEY)
+        .expect("Hmac can take a key of any size");
+    mac.input(salt);
+    mac.result().code().to_vec()
+}
@@ -0,0 +1,61 @@
+use crate::api_error::ApiError;
+use crate::db;
+use crate::schema::magic_link_token;
+use crate::user::User;
+use chrono::{NaiveDateTime, Utc, Duration};
+use diesel::prelude::*;
+use rand::Rng;
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+#[derive(Queryable, Insertable)]
+#[table_name = "magic_link_token"]
+pub struct MagicLinkToken {
+    pub id: Vec<u8>,
+    pub user_id: Uuid,
+    pub expires_at: NaiveDateTime,
+    pub created_at: NaiveDateTime,
+}
+
+impl MagicLinkToken {
+    // Only the hash of the token is stored, the returned secret is what gets sent to the user.
+    // Asking for a new link replaces the old one.
+    pub fn create(conn: &PgConnection, user_id: Uuid) -> Result<(Self, Vec<u8>), ApiError> {
+        let secret = rand::thread_rng().gen::<[u8; 32]>().to_vec();
+        let id = Sha256::digest(&secret).to_vec();
+        let created_at = Utc::now().naive_utc();
+        let expires_at = created_at + Duration::minutes(15);
+        let token = MagicLinkToken { id, user_id, expires_at, created_at };
+
+        let token = diesel::insert_into(magic_link_token::table)
+            .values(&token)
+            .on_conflict(magic_link_token::user_id)
+            .do_update()
+            .set((
+                magic_link_token::id.eq(&token.id),
+                magic_link_token::created_at.eq(&token.created_at),
+                magic_link_token::expires_at.eq(&token.expires_at),
+            ))
+            .get_result(conn)?;
+
+        Ok((token, secret))
+    }
+
+    // Deletes the token as it's read, so the link only works once
+    pub fn redeem(secret: &[u8]) -> Result<User, ApiError> {
+        let conn = db::connection()?;
+        let id = Sha256::digest(secret).to_vec();
+
+        conn.transaction(|| {
+            let token: MagicLinkToken = diesel::delete(
+                    magic_link_token::table
+                        .filter(magic_link_token::id.eq(&id))
+                        .filter(magic_link_token::expires_at.gt(Utc::now().naive_utc()))
+                )
+                .get_result(&conn)?;
+
+            let user = User::find(token.user_id)?;
+            Ok(user)
+        })
+    }
+}
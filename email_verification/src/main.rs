@@ -6,18 +6,36 @@ extern crate diesel;
 extern crate diesel_migrations;
 
 use actix_redis::RedisSession;
-use actix_web::{App, HttpServer};
+use actix_web::{web, App, HttpServer};
 use dotenv::dotenv;
 use listenfd::ListenFd;
 use std::env;
 
+mod active_session;
 mod api_error;
+mod api_key;
+mod cache;
+mod csrf;
 mod db;
 mod schema;
 mod auth;
+mod account;
 mod user;
 mod email;
+mod email_change_token;
+mod email_event;
+mod email_suppression;
+mod email_throttle;
 mod email_verification_token;
+mod magic_link_token;
+mod outbound_email;
+mod password_reset_token;
+mod two_factor;
+mod oidc;
+mod user_identity;
+
+#[cfg(test)]
+mod test;
 
 #[actix_rt::main]
 async fn main() -> std::io::Result<()> {
@@ -25,16 +43,49 @@ async fn main() -> std::io::Result<()> {
     env_logger::init();
 
     db::init();
+    cache::init();
+    user::init();
+    two_factor::init();
+    oidc::init();
+    email_verification_token::init();
+    email_throttle::init();
+
+    let email_config = email::EmailConfig::from_env()
+        .unwrap_or_else(|e| panic!("Invalid email config: {}", e));
+    let mailer = web::Data::new(email::Mailer::new(email_config)
+        .unwrap_or_else(|e| panic!("Failed to initialize email: {}", e)));
+
+    outbound_email::start_worker(mailer.clone());
+    email_verification_token::start_cleanup();
 
     let mut listenfd = ListenFd::from_env();
 
     let redis_port = env::var("REDIS_PORT").expect("Redis port not set");
     let redis_host = env::var("REDIS_HOST").expect("Redis host not set");
 
+    let csrf_exempt: Vec<String> = env::var("CSRF_EXEMPT_ROUTES")
+        .unwrap_or("".to_string())
+        .split(',')
+        .map(|path| path.trim().to_string())
+        .filter(|path| !path.is_empty())
+        .collect();
+
     let mut server = HttpServer::new(move|| 
         App::new()
+            .app_data(mailer.clone())
+            .wrap(csrf_exempt.iter().fold(csrf::Csrf::new(), |csrf, path| csrf.exempt(path.as_str())))
             .wrap(RedisSession::new(format!("{}:{}", redis_host, redis_port), &[0; 32]))
+            .configure(csrf::init_routes)
             .configure(auth::init_routes)
+            .configure(account::init_routes)
+            .configure(user::init_routes)
+            .configure(two_factor::init_routes)
+            .configure(active_session::init_routes)
+            .configure(oidc::init_routes)
+            .configure(api_key::init_routes)
+            .configure(outbound_email::init_routes)
+            .configure(email_event::init_routes)
+            .configure(email_suppression::init_routes)
     );
 
     server = match listenfd.take_tcp_listener(0)? {
@@ -0,0 +1,6 @@
+mod provider;
+mod routes;
+mod tests;
+
+pub use provider::{init, IdTokenClaims, OidcProvider};
+pub use routes::init_routes;
@@ -0,0 +1,287 @@
+use crate::api_error::ApiError;
+use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+use lazy_static::lazy_static;
+use reqwest::Url;
+use serde::Deserialize;
+use std::env;
+use std::sync::RwLock;
+use std::time::{Duration, Instant};
+
+// How long discovery and the signing keys are cached
+const METADATA_TTL: Duration = Duration::from_secs(60 * 60);
+// Allowed clock skew when checking the expiry of id tokens
+const LEEWAY_SECONDS: u64 = 60;
+
+lazy_static! {
+    static ref PROVIDER: Option<OidcProvider> = OidcProvider::from_env();
+}
+
+pub fn init() {
+    lazy_static::initialize(&PROVIDER);
+    if let Some(provider) = PROVIDER.as_ref() {
+        info!("Initializing OpenID Connect login with {}", provider.issuer);
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Timeouts {
+    pub connect: Duration,
+    pub read: Duration,
+}
+
+pub struct OidcProvider {
+    issuer: String,
+    client_id: String,
+    client_secret: String,
+    redirect_uri: String,
+    client: reqwest::Client,
+    metadata: RwLock<Option<Metadata>>,
+}
+
+#[derive(Clone)]
+struct Metadata {
+    discovery: Discovery,
+    jwks: Jwks,
+    fetched_at: Instant,
+}
+
+#[derive(Clone, Deserialize)]
+struct Discovery {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+}
+
+#[derive(Clone, Deserialize)]
+struct Jwks {
+    keys: Vec<Jwk>,
+}
+
+#[derive(Clone, Deserialize)]
+struct Jwk {
+    kid: Option<String>,
+    kty: String,
+    n: Option<String>,
+    e: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    id_token: String,
+}
+
+#[derive(Deserialize)]
+pub struct IdTokenClaims {
+    pub iss: String,
+    pub sub: String,
+    nonce: Option<String>,
+    pub email: Option<String>,
+    pub email_verified: Option<bool>,
+}
+
+impl OidcProvider {
+    fn from_env() -> Option<Self> {
+        let issuer = env::var("OIDC_ISSUER").ok()?;
+
+        let timeouts = Timeouts {
+            connect: Duration::from_secs(env_seconds("OIDC_CONNECT_TIMEOUT", 5)),
+            read: Duration::from_secs(env_seconds("OIDC_READ_TIMEOUT", 10)),
+        };
+
+        let provider = OidcProvider::new(
+            &issuer,
+            &env::var("OIDC_CLIENT_ID").expect("OIDC client id not set"),
+            &env::var("OIDC_CLIENT_SECRET").expect("OIDC client secret not set"),
+            &env::var("OIDC_REDIRECT_URI").expect("OIDC redirect uri not set"),
+            timeouts,
+        );
+
+        Some(provider.unwrap_or_else(|e| panic!("{}", e)))
+    }
+
+    // The provider is called with blocking requests, so callers should be on the blocking pool
+    pub fn new(issuer: &str, client_id: &str, client_secret: &str, redirect_uri: &str, timeouts: Timeouts) -> Result<Self, String> {
+        if !is_secure(issuer) {
+            return Err(format!("OIDC issuer must use https: {}", issuer));
+        }
+
+        let client = reqwest::Client::builder()
+            .connect_timeout(timeouts.connect)
+            .timeout(timeouts.read)
+            .build()
+            .map_err(|e| format!("Failed to create OIDC client: {}", e))?;
+
+        Ok(OidcProvider {
+            issuer: issuer.trim_end_matches('/').to_string(),
+            client_id: client_id.to_string(),
+            client_secret: client_secret.to_string(),
+            redirect_uri: redirect_uri.to_string(),
+            client,
+            metadata: RwLock::new(None),
+        })
+    }
+
+    pub fn get() -> Result<&'static Self, ApiError> {
+        PROVIDER.as_ref()
+            .ok_or(ApiError::new(404, "OpenID Connect login is not configured"))
+    }
+
+    pub fn authorize_url(&self, state: &str, nonce: &str, code_challenge: &str) -> Result<String, ApiError> {
+        let discovery = self.metadata(false)?.discovery;
+
+        let url = Url::parse_with_params(&discovery.authorization_endpoint, &[
+                ("response_type", "code"),
+                ("client_id", self.client_id.as_str()),
+                ("redirect_uri", self.redirect_uri.as_str()),
+                ("scope", "openid email"),
+                ("state", state),
+                ("nonce", nonce),
+                ("code_challenge", code_challenge),
+                ("code_challenge_method", "S256"),
+            ])
+            .map_err(|e| ApiError::new(500, format!("Invalid OIDC authorization endpoint: {}", e)))?;
+
+        Ok(url.into_string())
+    }
+
+    pub fn exchange_code(&self, code: &str, code_verifier: &str, nonce: &str) -> Result<IdTokenClaims, ApiError> {
+        let metadata = self.metadata(false)?;
+
+        let mut response = self.client.post(&metadata.discovery.token_endpoint)
+            .header("Accept", "application/json")
+            .form(&[
+                ("grant_type", "authorization_code"),
+                ("code", code),
+                ("redirect_uri", self.redirect_uri.as_str()),
+                ("client_id", self.client_id.as_str()),
+                ("client_secret", self.client_secret.as_str()),
+                ("code_verifier", code_verifier),
+            ])
+            .send()
+            .map_err(|e| ApiError::new(500, format!("Failed to exchange OIDC code: {}", e)))?;
+
+        if !response.status().is_success() {
+            return Err(ApiError::new(401, "The identity provider rejected the authorization code"));
+        }
+
+        let token: TokenResponse = response
+            .json()
+            .map_err(|e| ApiError::new(500, format!("Failed to read OIDC token response: {}", e)))?;
+
+        self.verify(&token.id_token, metadata, nonce)
+    }
+
+    // Checks the signature against the provider's keys, and the issuer, audience, expiry and nonce
+    fn verify(&self, id_token: &str, metadata: Metadata, nonce: &str) -> Result<IdTokenClaims, ApiError> {
+        let header = jsonwebtoken::decode_header(id_token)
+            .map_err(|_| ApiError::new(401, "Invalid id token"))?;
+
+        match header.alg {
+            Algorithm::RS256 | Algorithm::RS384 | Algorithm::RS512 => (),
+            _ => return Err(ApiError::new(401, "Invalid id token")),
+        }
+
+        // The provider may have rotated its keys since they were fetched
+        let metadata = match find_key(&metadata.jwks, &header.kid) {
+            Some(_) => metadata,
+            None => self.metadata(true)?,
+        };
+
+        let (n, e) = find_key(&metadata.jwks, &header.kid)
+            .ok_or(ApiError::new(401, "Invalid id token"))?;
+
+        let mut validation = Validation::new(header.alg);
+        validation.leeway = LEEWAY_SECONDS;
+        validation.iss = Some(metadata.discovery.issuer.clone());
+        validation.set_audience(&[&self.client_id]);
+
+        let claims: IdTokenClaims = jsonwebtoken::decode(id_token, &DecodingKey::from_rsa_components(n, e), &validation)
+            .map_err(|_| ApiError::new(401, "Invalid id token"))?
+            .claims;
+
+        if claims.nonce.as_deref() != Some(nonce) {
+            return Err(ApiError::new(401, "Invalid id token"));
+        }
+
+        Ok(claims)
+    }
+
+    fn metadata(&self, refresh: bool) -> Result<Metadata, ApiError> {
+        if !refresh {
+            let cached = self.metadata.read()
+                .map_err(|_| ApiError::new(500, "OIDC metadata lock poisoned"))?;
+
+            if let Some(metadata) = cached.as_ref() {
+                if metadata.fetched_at.elapsed() < METADATA_TTL {
+                    return Ok(metadata.clone());
+                }
+            }
+        }
+
+        let discovery = self.discover()?;
+
+        let jwks: Jwks = self.client.get(&discovery.jwks_uri)
+            .send()
+            .and_then(|mut response| response.json())
+            .map_err(|e| ApiError::new(500, format!("Failed to fetch OIDC signing keys: {}", e)))?;
+
+        let metadata = Metadata { discovery, jwks, fetched_at: Instant::now() };
+
+        *self.metadata.write().map_err(|_| ApiError::new(500, "OIDC metadata lock poisoned"))? = Some(metadata.clone());
+
+        Ok(metadata)
+    }
+
+    fn discover(&self) -> Result<Discovery, ApiError> {
+        let url = format!("{}/.well-known/openid-configuration", self.issuer);
+
+        let discovery: Discovery = self.client.get(&url)
+            .send()
+            .and_then(|mut response| response.json())
+            .map_err(|e| ApiError::new(500, format!("Failed to discover OIDC provider: {}", e)))?;
+
+        if discovery.issuer.trim_end_matches('/') != self.issuer {
+            return Err(ApiError::new(500, format!("OIDC issuer mismatch: {}", discovery.issuer)));
+        }
+
+        let endpoints = [&discovery.authorization_endpoint, &discovery.token_endpoint, &discovery.jwks_uri];
+        if let Some(endpoint) = endpoints.iter().find(|endpoint| !is_secure(endpoint)) {
+            return Err(ApiError::new(500, format!("OIDC endpoint must use https: {}", endpoint)));
+        }
+
+        Ok(discovery)
+    }
+}
+
+fn env_seconds(key: &str, default: u64) -> u64 {
+    match env::var(key) {
+        Ok(seconds) => seconds.parse().unwrap_or_else(|_| panic!("{} must be a number", key)),
+        Err(_) => default,
+    }
+}
+
+// Returns the modulus and exponent of the RSA key the token was signed with
+fn find_key<'a>(jwks: &'a Jwks, kid: &Option<String>) -> Option<(&'a str, &'a str)> {
+    jwks.keys.iter()
+        .filter(|jwk| jwk.kty == "RSA")
+        .filter(|jwk| kid.is_none() || &jwk.kid == kid)
+        .find_map(|jwk| match (&jwk.n, &jwk.e) {
+            (Some(n), Some(e)) => Some((n.as_str(), e.as_str())),
+            _ => None,
+        })
+}
+
+// Tests run against a mock issuer on localhost, which doesn't have a certificate
+fn is_secure(url: &str) -> bool {
+    let url = match Url::parse(url) {
+        Ok(url) => url,
+        Err(_) => return false,
+    };
+
+    match url.scheme() {
+        "https" => true,
+        "http" => cfg!(test) && url.host_str() == Some("127.0.0.1"),
+        _ => false,
+    }
+}
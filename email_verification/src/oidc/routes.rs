@@ -0,0 +1,102 @@
+use crate::api_error::ApiError;
+use crate::auth;
+use crate::db;
+use crate::oidc::{IdTokenClaims, OidcProvider};
+use crate::user::{User, UserMessage};
+use crate::user_identity::UserIdentity;
+use actix_web::{get, web, HttpRequest, HttpResponse};
+use actix_session::Session;
+use diesel::Connection;
+use rand::Rng;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+#[get("/sign-in/oidc")]
+async fn sign_in(session: Session) -> Result<HttpResponse, ApiError> {
+    let provider = OidcProvider::get()?;
+
+    let state = random_string();
+    let nonce = random_string();
+    let code_verifier = random_string();
+    let code_challenge = base64::encode_config(&Sha256::digest(code_verifier.as_bytes()), base64::URL_SAFE_NO_PAD);
+
+    // Discovery goes to the identity provider, which may be slow to answer
+    let url = {
+        let (state, nonce) = (state.clone(), nonce.clone());
+        web::block(move || provider.authorize_url(&state, &nonce, &code_challenge)).await?
+    };
+
+    session.set("oidc_state", state)?;
+    session.set("oidc_nonce", nonce)?;
+    session.set("oidc_code_verifier", code_verifier)?;
+
+    Ok(HttpResponse::Found().header("Location", url).finish())
+}
+
+#[derive(Deserialize)]
+struct CallbackQuery {
+    code: String,
+    state: String,
+}
+
+#[get("/sign-in/oidc/callback")]
+async fn callback(query: web::Query<CallbackQuery>, session: Session, req: HttpRequest) -> Result<HttpResponse, ApiError> {
+    let provider = OidcProvider::get()?;
+    let query = query.into_inner();
+
+    let state: Option<String> = session.get("oidc_state")?;
+    let nonce: Option<String> = session.get("oidc_nonce")?;
+    let code_verifier: Option<String> = session.get("oidc_code_verifier")?;
+
+    session.remove("oidc_state");
+    session.remove("oidc_nonce");
+    session.remove("oidc_code_verifier");
+
+    let (nonce, code_verifier) = match (state, nonce, code_verifier) {
+        (Some(state), Some(nonce), Some(code_verifier)) if state == query.state => (nonce, code_verifier),
+        _ => return Err(ApiError::new(401, "Invalid state")),
+    };
+
+    let claims = web::block(move || provider.exchange_code(&query.code, &code_verifier, &nonce)).await?;
+    let user = web::block(move || find_or_create_user(claims)).await?;
+
+    auth::sign_in_user(user, &session, &req)
+}
+
+fn find_or_create_user(claims: IdTokenClaims) -> Result<User, ApiError> {
+    match UserIdentity::find(&claims.iss, &claims.sub) {
+        Ok(identity) => return User::find(identity.user_id),
+        Err(e) if e.status_code == 404 => (),
+        Err(e) => return Err(e),
+    }
+
+    // Only link to an existing account when the provider vouches for the email
+    let email = match (claims.email, claims.email_verified) {
+        (Some(email), Some(true)) => email,
+        _ => return Err(ApiError::new(403, "The identity provider did not supply a verified email")),
+    };
+
+    let conn = db::connection()?;
+
+    // A new user is only kept if it could be linked, so a failed or concurrent
+    // callback doesn't leave behind an account nobody can sign in to
+    conn.transaction(|| {
+        let user = match User::find_by_email(email.clone()) {
+            Ok(user) => user,
+            Err(e) if e.status_code == 404 => User::create(&conn, UserMessage { email, password: random_string() })?,
+            Err(e) => return Err(e),
+        };
+
+        UserIdentity::create(&conn, user.id, claims.iss, claims.sub)?;
+        Ok(user)
+    })
+}
+
+fn random_string() -> String {
+    base64::encode_config(&rand::thread_rng().gen::<[u8; 32]>(), base64::URL_SAFE_NO_PAD)
+}
+
+pub fn init_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(sign_in);
+    cfg.service(callback);
+}
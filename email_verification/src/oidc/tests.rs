@@ -0,0 +1,190 @@
+
+#[cfg(test)]
+mod tests {
+    use crate::oidc::OidcProvider;
+    use crate::oidc::provider::Timeouts;
+    use chrono::Utc;
+    use jsonwebtoken::{Algorithm, EncodingKey, Header};
+    use serde_json::{json, Value};
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    // The public half of test_key.pem
+    const MODULUS: &str = "zLiRbRFXHmkE0MO-W_NzAolcAGXpcxB_5QqCQReR8_SwRWVqknCda_jrd_s8jZXuSVAPB5XtfXDFOfEfD2k9LJSGV3Ht_mWHjmWj1yw9s7aLztaSt-iqGMaFzNRCEui1KY_CD1PzePYVo6kjcADSD_OZDe9RDLbLaV3gaynEdrYVweIKv1DgXziN-WoH0hhYPA2Fq5RklNYq8yFemhYdWKO0K-S82NAKXOyFPJBpvFl2xh2E-JvmC9V-6nv8eREiDgjo2E55Cxzne-imNzj5T-_3HEeJqQxSm8akXKDO-ojoVEOtY5dFYrTVAp4KMjBMiUqzg4Gh4jhXTyE-D-8Ocw";
+    const KEY_ID: &str = "test";
+
+    // Serves discovery, the signing keys and a token endpoint that answers with the given id token
+    struct MockIssuer {
+        url: String,
+        id_token: Arc<Mutex<String>>,
+        discoveries: Arc<AtomicUsize>,
+    }
+
+    impl MockIssuer {
+        fn start() -> Self {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            let url = format!("http://127.0.0.1:{}", listener.local_addr().unwrap().port());
+            let issuer = MockIssuer {
+                url: url.clone(),
+                id_token: Arc::new(Mutex::new(String::new())),
+                discoveries: Arc::new(AtomicUsize::new(0)),
+            };
+
+            let id_token = issuer.id_token.clone();
+            let discoveries = issuer.discoveries.clone();
+            thread::spawn(move || {
+                for stream in listener.incoming().filter_map(|stream| stream.ok()) {
+                    MockIssuer::handle(stream, &url, &id_token, &discoveries);
+                }
+            });
+
+            issuer
+        }
+
+        fn handle(mut stream: TcpStream, url: &str, id_token: &Mutex<String>, discoveries: &AtomicUsize) {
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).unwrap();
+            let path = request_line.split_whitespace().nth(1).unwrap_or("").to_string();
+
+            let mut content_length = 0;
+            loop {
+                let mut line = String::new();
+                if reader.read_line(&mut line).unwrap() == 0 || line == "\r\n" {
+                    break;
+                }
+
+                let line = line.to_lowercase();
+                if line.starts_with("content-length:") {
+                    content_length = line["content-length:".len()..].trim().parse().unwrap_or(0);
+                }
+            }
+
+            let mut body = vec![0; content_length];
+            reader.read_exact(&mut body).unwrap();
+
+            let (status, body) = match path.as_str() {
+                "/.well-known/openid-configuration" => {
+                    discoveries.fetch_add(1, Ordering::SeqCst);
+                    ("200 OK", json!({
+                        "issuer": url,
+                        "authorization_endpoint": format!("{}/authorize", url),
+                        "token_endpoint": format!("{}/token", url),
+                        "jwks_uri": format!("{}/jwks", url),
+                    }))
+                },
+                "/jwks" => ("200 OK", json!({
+                    "keys": [{ "kty": "RSA", "kid": KEY_ID, "alg": "RS256", "use": "sig", "n": MODULUS, "e": "AQAB" }],
+                })),
+                "/token" => ("200 OK", json!({ "id_token": *id_token.lock().unwrap(), "token_type": "Bearer" })),
+                _ => ("404 Not Found", json!({})),
+            };
+
+            let body = body.to_string();
+            write!(stream, "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}", status, body.len(), body).unwrap();
+        }
+
+        fn issue(&self, id_token: String) {
+            *self.id_token.lock().unwrap() = id_token;
+        }
+
+        fn claims(&self) -> Value {
+            let now = Utc::now().timestamp();
+            json!({
+                "iss": self.url,
+                "sub": "subject",
+                "aud": "client",
+                "iat": now,
+                "exp": now + 300,
+                "nonce": "nonce",
+                "email": "oidc@cloudmaker.dev",
+                "email_verified": true,
+            })
+        }
+    }
+
+    fn timeouts() -> Timeouts {
+        Timeouts { connect: Duration::from_secs(1), read: Duration::from_secs(1) }
+    }
+
+    fn sign(claims: &Value, kid: &str) -> String {
+        let mut header = Header::new(Algorithm::RS256);
+        header.kid = Some(kid.to_string());
+        let key = EncodingKey::from_rsa_pem(include_bytes!("test_key.pem")).unwrap();
+        jsonwebtoken::encode(&header, claims, &key).unwrap()
+    }
+
+    #[test]
+    fn test_id_token_is_verified() {
+        let issuer = MockIssuer::start();
+        let provider = OidcProvider::new(&issuer.url, "client", "secret", "http://127.0.0.1/callback", timeouts()).unwrap();
+
+        for _ in 0..2 {
+            issuer.issue(sign(&issuer.claims(), KEY_ID));
+            let claims = provider.exchange_code("code", "verifier", "nonce").expect("Valid id token was rejected");
+            assert_eq!("subject", claims.sub);
+        }
+        assert_eq!(1, issuer.discoveries.load(Ordering::SeqCst), "Discovery should be cached");
+
+        let now = Utc::now().timestamp();
+        let invalid = vec![
+            ("nonce", json!("other"), "Wrong nonce"),
+            ("aud", json!("other"), "Wrong audience"),
+            ("iss", json!("https://idp.example.com"), "Wrong issuer"),
+            ("exp", json!(now - 3600), "Expired token"),
+        ];
+
+        for (claim, value, reason) in invalid {
+            let mut claims = issuer.claims();
+            claims[claim] = value;
+            issuer.issue(sign(&claims, KEY_ID));
+            let res = provider.exchange_code("code", "verifier", "nonce");
+            assert_eq!(Some(401), res.err().map(|e| e.status_code), "{} should be rejected", reason);
+        }
+
+        // The claims of a valid token swapped for others, keeping the signature
+        let token = sign(&issuer.claims(), KEY_ID);
+        let mut claims = issuer.claims();
+        claims["sub"] = json!("someone else");
+        let forged = sign(&claims, KEY_ID);
+        let parts: Vec<&str> = token.split('.').collect();
+        let forged_parts: Vec<&str> = forged.split('.').collect();
+        issuer.issue(format!("{}.{}.{}", parts[0], forged_parts[1], parts[2]));
+        let res = provider.exchange_code("code", "verifier", "nonce");
+        assert_eq!(Some(401), res.err().map(|e| e.status_code), "Token with a bad signature should be rejected");
+
+        let unsigned = format!("{}.{}.", base64::encode_config(r#"{"alg":"none"}"#, base64::URL_SAFE_NO_PAD), forged_parts[1]);
+        issuer.issue(unsigned);
+        let res = provider.exchange_code("code", "verifier", "nonce");
+        assert_eq!(Some(401), res.err().map(|e| e.status_code), "Unsigned token should be rejected");
+
+        issuer.issue(sign(&issuer.claims(), "unknown"));
+        let res = provider.exchange_code("code", "verifier", "nonce");
+        assert_eq!(Some(401), res.err().map(|e| e.status_code), "Token signed with an unknown key should be rejected");
+        assert_eq!(2, issuer.discoveries.load(Ordering::SeqCst), "An unknown key should refresh the signing keys");
+    }
+
+    #[test]
+    fn test_issuer_must_use_https() {
+        assert!(OidcProvider::new("http://idp.example.com", "client", "secret", "https://app.example.com/callback", timeouts()).is_err());
+        assert!(OidcProvider::new("ftp://idp.example.com", "client", "secret", "https://app.example.com/callback", timeouts()).is_err());
+        assert!(OidcProvider::new("https://idp.example.com", "client", "secret", "https://app.example.com/callback", timeouts()).is_ok());
+    }
+
+    #[test]
+    fn test_requests_to_the_provider_time_out() {
+        // An issuer that takes the connection but never answers
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://127.0.0.1:{}", listener.local_addr().unwrap().port());
+        let provider = OidcProvider::new(&url, "client", "secret", "http://127.0.0.1/callback", timeouts()).unwrap();
+
+        let started = Instant::now();
+        let res = provider.authorize_url("state", "nonce", "challenge");
+        assert!(res.is_err(), "A hung issuer should fail the request");
+        assert!(started.elapsed() < Duration::from_secs(10), "Requests to the issuer should give up after the read timeout");
+    }
+}
@@ -0,0 +1,110 @@
+use crate::api_error::ApiError;
+use crate::db;
+use crate::email::{Email, Mailer};
+use crate::email_change_token::EmailChangeToken;
+use crate::email_suppression::EmailSuppression;
+use crate::email_verification_token::EmailVerificationToken;
+use crate::magic_link_token::MagicLinkToken;
+use crate::outbound_email::OutboundEmail;
+use crate::password_reset_token::PasswordResetToken;
+use crate::user::User;
+use diesel::PgConnection;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use uuid::Uuid;
+
+// What goes in the outbox. Emails with a link are rendered when they are sent, and the token in
+// the link is either derived from its salt or created then, so the outbox never holds a secret.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum OutboundMessage {
+    Invite { email: String, locale: String },
+    ChangeEmail { user_id: Uuid, locale: String },
+    // These two are queued by email for any address, so the request takes the same time whether
+    // or not it has an account
+    PasswordReset { email: String, locale: String },
+    MagicLink { email: String, locale: String },
+}
+
+impl OutboundMessage {
+    // Adds the message to the outbox, it is sent by the worker once the transaction commits
+    pub fn queue(&self, conn: &PgConnection) -> Result<Uuid, ApiError> {
+        let outbound_email = OutboundEmail::enqueue(conn, self)?;
+        Ok(outbound_email.id)
+    }
+
+    // Returns None when there is nothing left to send, e.g. the token has been used or has expired
+    pub fn render(&self, mailer: &Mailer) -> Result<Option<Email>, ApiError> {
+        let conn = db::connection()?;
+
+        match self {
+            OutboundMessage::Invite { email, locale } => {
+                let secret = match found(EmailVerificationToken::find_unexpired(&conn, email))? {
+                    Some(token) => token.secret(),
+                    None => None,
+                };
+
+                let secret = match secret {
+                    Some(secret) => secret,
+                    None => return Ok(None),
+                };
+
+                let link = mailer.frontend_link("/register", &[("token", hex::encode(secret).as_str()), ("email", email.as_str())])?;
+                let email = mailer.compose("invite", locale, &json!({ "link": link }))?
+                    .add_recipient(email.as_str());
+
+                Ok(Some(email))
+            },
+            OutboundMessage::ChangeEmail { user_id, locale } => {
+                let token = match found(EmailChangeToken::find_unexpired(&conn, *user_id))? {
+                    Some(token) => token,
+                    None => return Ok(None),
+                };
+
+                let link = mailer.frontend_link("/account/email/confirm", &[("token", hex::encode(token.secret()).as_str())])?;
+                let email = mailer.compose("change_email", locale, &json!({ "link": link }))?
+                    .add_recipient(token.email);
+
+                Ok(Some(email))
+            },
+            OutboundMessage::PasswordReset { email, locale } => {
+                let user = match found(User::find_by_email(email.clone()))? {
+                    Some(user) => user,
+                    None => return Ok(None),
+                };
+
+                let (_, secret) = PasswordResetToken::create(&conn, user.id)?;
+                let link = mailer.frontend_link("/password-reset", &[("token", hex::encode(secret).as_str())])?;
+                let email = mailer.compose("password_reset", locale, &json!({ "link": link }))?
+                    .add_recipient(user.email);
+
+                Ok(Some(email))
+            },
+            OutboundMessage::MagicLink { email, locale } => {
+                let user = match found(User::find_by_email(email.clone()))? {
+                    Some(user) => user,
+                    None => return Ok(None),
+                };
+
+                if EmailSuppression::is_suppressed(&user.email)? {
+                    return Ok(None);
+                }
+
+                let (_, secret) = MagicLinkToken::create(&conn, user.id)?;
+                let link = mailer.link(&format!("/sign-in/magic-link/{}", hex::encode(secret)), &[])?;
+                let email = mailer.compose("magic_link", locale, &json!({ "link": link }))?
+                    .add_recipient(user.email);
+
+                Ok(Some(email))
+            },
+        }
+    }
+}
+
+fn found<T>(res: Result<T, ApiError>) -> Result<Option<T>, ApiError> {
+    match res {
+        Ok(value) => Ok(Some(value)),
+        Err(e) if e.status_code == 404 => Ok(None),
+        Err(e) => Err(e),
+    }
+}
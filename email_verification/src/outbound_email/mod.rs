@@ -0,0 +1,12 @@
+mod message;
+mod model;
+mod routes;
+mod tests;
+mod worker;
+
+pub use message::OutboundMessage;
+pub use model::OutboundEmail;
+pub use routes::init_routes;
+pub use worker::{process_due, start as start_worker};
+#[cfg(test)]
+pub use worker::process_due_for;
@@ -0,0 +1,171 @@
+use crate::api_error::ApiError;
+use crate::db;
+use crate::outbound_email::OutboundMessage;
+use crate::schema::outbound_email;
+use chrono::{Duration, NaiveDateTime, Utc};
+use diesel::prelude::*;
+use serde::Serialize;
+use uuid::Uuid;
+
+const MAX_ATTEMPTS: i32 = 8;
+const BASE_BACKOFF_SECONDS: i64 = 30;
+const MAX_BACKOFF_SECONDS: i64 = 3600;
+// How long a claimed email is hidden from other workers while it's being sent
+const CLAIM_SECONDS: i64 = 300;
+
+#[derive(Serialize, Queryable)]
+pub struct OutboundEmail {
+    pub id: Uuid,
+    #[serde(skip_serializing)]
+    pub payload: String,
+    pub status: String,
+    pub attempts: i32,
+    pub next_attempt_at: NaiveDateTime,
+    pub last_error: Option<String>,
+    pub message_id: Option<String>,
+    pub sent_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+}
+
+impl OutboundEmail {
+    // Takes the connection so the email is only queued if the surrounding transaction commits
+    pub fn enqueue(conn: &PgConnection, message: &OutboundMessage) -> Result<Self, ApiError> {
+        let payload = serde_json::to_string(message)
+            .map_err(|e| ApiError::new(500, format!("Failed to serialize email: {}", e)))?;
+
+        let outbound_email = diesel::insert_into(outbound_email::table)
+            .values(outbound_email::payload.eq(payload))
+            .get_result(conn)?;
+
+        Ok(outbound_email)
+    }
+
+    // A payload that can't be read never will be, so it's dead lettered right away
+    pub fn message(&self) -> Result<OutboundMessage, ApiError> {
+        serde_json::from_str(&self.payload)
+            .map_err(|e| ApiError::new(422, format!("Failed to deserialize email: {}", e)))
+    }
+
+    // Locks the due emails and pushes them back, so other workers skip them while we send
+    pub fn claim_due(limit: i64) -> Result<Vec<Self>, ApiError> {
+        OutboundEmail::claim_due_matching("%", limit)
+    }
+
+    // Only claims the emails to the recipient, so tests sharing the table leave each other's emails alone
+    #[cfg(test)]
+    pub fn claim_due_for(recipient: &str, limit: i64) -> Result<Vec<Self>, ApiError> {
+        OutboundEmail::claim_due_matching(&format!("%{}%", recipient), limit)
+    }
+
+    fn claim_due_matching(payload: &str, limit: i64) -> Result<Vec<Self>, ApiError> {
+        let conn = db::connection()?;
+        let now = Utc::now().naive_utc();
+
+        conn.transaction(|| {
+            let ids: Vec<Uuid> = outbound_email::table
+                .select(outbound_email::id)
+                .filter(outbound_email::status.eq("pending"))
+                .filter(outbound_email::next_attempt_at.le(now))
+                .filter(outbound_email::payload.like(payload))
+                .order(outbound_email::next_attempt_at.asc())
+                .limit(limit)
+                .for_update()
+                .skip_locked()
+                .load(&conn)?;
+
+            let emails = diesel::update(outbound_email::table.filter(outbound_email::id.eq_any(ids)))
+                .set(outbound_email::next_attempt_at.eq(now + Duration::seconds(CLAIM_SECONDS)))
+                .get_results(&conn)?;
+
+            Ok(emails)
+        })
+    }
+
+    pub fn mark_sent(id: Uuid, message_id: String) -> Result<Self, ApiError> {
+        let conn = db::connection()?;
+
+        let outbound_email = diesel::update(outbound_email::table.filter(outbound_email::id.eq(id)))
+            .set((
+                outbound_email::status.eq("sent"),
+                outbound_email::attempts.eq(outbound_email::attempts + 1),
+                outbound_email::message_id.eq(message_id),
+                outbound_email::sent_at.eq(Utc::now().naive_utc()),
+            ))
+            .get_result(&conn)?;
+
+        Ok(outbound_email)
+    }
+
+    // There was nothing to send, e.g. because the token in the link was used in the meantime
+    pub fn mark_skipped(id: Uuid) -> Result<Self, ApiError> {
+        let conn = db::connection()?;
+
+        let outbound_email = diesel::update(outbound_email::table.filter(outbound_email::id.eq(id)))
+            .set((
+                outbound_email::status.eq("skipped"),
+                outbound_email::attempts.eq(outbound_email::attempts + 1),
+            ))
+            .get_result(&conn)?;
+
+        Ok(outbound_email)
+    }
+
+    // Rejected emails go straight to the dead letters, anything else is retried with backoff
+    pub fn mark_failed(id: Uuid, attempts: i32, error: &ApiError) -> Result<Self, ApiError> {
+        let conn = db::connection()?;
+        let attempts = attempts + 1;
+
+        let status = match is_rejected(error.status_code) || attempts >= MAX_ATTEMPTS {
+            true => "dead",
+            false => "pending",
+        };
+
+        let backoff = (BASE_BACKOFF_SECONDS << (attempts - 1).min(16)).min(MAX_BACKOFF_SECONDS);
+
+        let outbound_email = diesel::update(outbound_email::table.filter(outbound_email::id.eq(id)))
+            .set((
+                outbound_email::status.eq(status),
+                outbound_email::attempts.eq(attempts),
+                outbound_email::last_error.eq(&error.message),
+                outbound_email::next_attempt_at.eq(Utc::now().naive_utc() + Duration::seconds(backoff)),
+            ))
+            .get_result(&conn)?;
+
+        Ok(outbound_email)
+    }
+
+    pub fn find_dead() -> Result<Vec<Self>, ApiError> {
+        let conn = db::connection()?;
+
+        let emails = outbound_email::table
+            .filter(outbound_email::status.eq("dead"))
+            .order(outbound_email::created_at.desc())
+            .load(&conn)?;
+
+        Ok(emails)
+    }
+
+    pub fn retry(id: Uuid) -> Result<Self, ApiError> {
+        let conn = db::connection()?;
+
+        let outbound_email = diesel::update(
+                outbound_email::table
+                    .filter(outbound_email::id.eq(id))
+                    .filter(outbound_email::status.eq("dead"))
+            )
+            .set((
+                outbound_email::status.eq("pending"),
+                outbound_email::attempts.eq(0),
+                outbound_email::next_attempt_at.eq(Utc::now().naive_utc()),
+            ))
+            .get_result(&conn)?;
+
+        Ok(outbound_email)
+    }
+}
+
+// The provider won't ever accept the email, e.g. because the address is invalid. Errors like a
+// rotated api key (401), a timeout (408) or rate limiting (429) may go away, so they are retried.
+fn is_rejected(status_code: u16) -> bool {
+    status_code == 400 || status_code == 422
+}
@@ -0,0 +1,26 @@
+use crate::api_error::ApiError;
+use crate::auth::Authenticated;
+use crate::outbound_email::OutboundEmail;
+use actix_web::{get, post, web, HttpResponse};
+use uuid::Uuid;
+
+#[get("/admin/outbound-emails/dead")]
+async fn find_dead(auth: Authenticated) -> Result<HttpResponse, ApiError> {
+    auth.require_admin()?;
+
+    let emails = OutboundEmail::find_dead()?;
+    Ok(HttpResponse::Ok().json(emails))
+}
+
+#[post("/admin/outbound-emails/{id}/retry")]
+async fn retry(id: web::Path<Uuid>, auth: Authenticated) -> Result<HttpResponse, ApiError> {
+    auth.require_admin()?;
+
+    let email = OutboundEmail::retry(id.into_inner())?;
+    Ok(HttpResponse::Ok().json(email))
+}
+
+pub fn init_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(find_dead);
+    cfg.service(retry);
+}
@@ -0,0 +1,200 @@
+
+#[cfg(test)]
+mod tests {
+    use crate::account;
+    use crate::api_error::ApiError;
+    use crate::api_key::{ApiKey, ApiKeyMessage};
+    use crate::auth;
+    use crate::db;
+    use crate::email::FRONTEND_ROUTES;
+    use crate::email_change_token::EmailChangeToken;
+    use crate::email_verification_token::{EmailVerificationToken, EmailVerificationTokenMessage};
+    use crate::outbound_email::{init_routes, OutboundEmail, OutboundMessage};
+    use crate::schema::{outbound_email, user};
+    use crate::user::{User, UserMessage};
+    use actix_redis::RedisSession;
+    use actix_web::{test::{self, TestRequest}, App};
+    use chrono::Utc;
+    use diesel::prelude::*;
+    use reqwest::Url;
+    use serde_json::{json, Value};
+    use std::env;
+    use uuid::Uuid;
+
+    // The unique recipient keeps the email out of the outbox processing in other tests
+    fn enqueue() -> OutboundEmail {
+        let conn = db::connection().unwrap();
+        let message = OutboundMessage::Invite {
+            email: format!("{}@cloudmaker.dev", Uuid::new_v4()),
+            locale: "en".to_string(),
+        };
+        OutboundEmail::enqueue(&conn, &message).expect("Failed to queue email")
+    }
+
+    fn delete(id: Uuid) {
+        let conn = db::connection().unwrap();
+        diesel::delete(outbound_email::table.filter(outbound_email::id.eq(id)))
+            .execute(&conn)
+            .expect("Failed to delete email");
+    }
+
+    fn delay(email: &OutboundEmail) -> i64 {
+        (email.next_attempt_at - Utc::now().naive_utc()).num_seconds()
+    }
+
+    #[test]
+    fn test_failures_back_off_until_dead_lettered() {
+        crate::test::init();
+        let email = enqueue();
+
+        let email = OutboundEmail::mark_failed(email.id, email.attempts, &ApiError::new(503, "Unavailable")).unwrap();
+        assert_eq!(("pending", 1), (email.status.as_str(), email.attempts));
+        assert!((28..=30).contains(&delay(&email)), "First retry should be in 30 seconds");
+
+        // A rotated api key, a timeout or rate limiting may go away
+        for status_code in &[401, 408, 429] {
+            let failed = OutboundEmail::mark_failed(email.id, 1, &ApiError::new(*status_code, "Try again")).unwrap();
+            assert_eq!("pending", failed.status, "{} should be retried", status_code);
+            assert!((58..=60).contains(&delay(&failed)), "Second retry should be in 60 seconds");
+        }
+
+        let email = OutboundEmail::mark_failed(email.id, 6, &ApiError::new(503, "Unavailable")).unwrap();
+        assert_eq!("pending", email.status);
+        assert!((1918..=1920).contains(&delay(&email)), "Backoff should double with every attempt");
+
+        let email = OutboundEmail::mark_failed(email.id, 7, &ApiError::new(503, "Unavailable")).unwrap();
+        assert_eq!(("dead", 8), (email.status.as_str(), email.attempts), "Should give up after 8 attempts");
+        assert_eq!(Some("Unavailable".to_string()), email.last_error);
+
+        delete(email.id);
+    }
+
+    #[test]
+    fn test_rejections_are_dead_lettered_right_away() {
+        crate::test::init();
+
+        for status_code in &[400, 422] {
+            let email = enqueue();
+            let email = OutboundEmail::mark_failed(email.id, 0, &ApiError::new(*status_code, "Rejected")).unwrap();
+            assert_eq!(("dead", 1), (email.status.as_str(), email.attempts), "{} should not be retried", status_code);
+            delete(email.id);
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_retry_dead_email() {
+        crate::test::init();
+
+        let conn = db::connection().unwrap();
+        let email = format!("{}@cloudmaker.dev", Uuid::new_v4());
+        let admin = User::create(&conn, UserMessage { email: email.clone(), password: "test".to_string() }).unwrap();
+
+        let redis_port = env::var("REDIS_PORT").expect("Redis port not set");
+        let redis_host = env::var("REDIS_HOST").expect("Redis host not set");
+
+        let mut app = test::init_service(
+            App::new()
+                .wrap(RedisSession::new(format!("{}:{}", redis_host, redis_port), &[0; 32]))
+                .configure(auth::init_routes)
+                .configure(init_routes)
+        ).await;
+
+        let req = TestRequest::post().uri("/sign-in").set_json(&json!({ "email": email, "password": "test" })).to_request();
+        let resp = test::call_service(&mut app, req).await;
+        let cookie = resp.response().cookies().next().expect("No session cookie").into_owned();
+
+        let dead = enqueue();
+        let dead = OutboundEmail::mark_failed(dead.id, 0, &ApiError::new(422, "Rejected")).unwrap();
+        let uri = format!("/admin/outbound-emails/{}/retry", dead.id);
+
+        let req = TestRequest::post().uri(&uri).cookie(cookie.clone()).to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(403, resp.status().as_u16(), "Only admins can retry emails");
+
+        let (_, secret) = ApiKey::create(admin.id, ApiKeyMessage { name: "test".to_string(), scopes: vec![], expires_at: None }).unwrap();
+        let authorization = format!("ApiKey {}", secret);
+        let req = TestRequest::get().uri("/admin/outbound-emails/dead").header("Authorization", authorization.as_str()).to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(403, resp.status().as_u16(), "Only admins can list dead emails with an api key");
+
+        diesel::update(user::table.filter(user::id.eq(admin.id)))
+            .set(user::is_admin.eq(true))
+            .execute(&conn)
+            .unwrap();
+
+        let req = TestRequest::get().uri("/admin/outbound-emails/dead").header("Authorization", authorization.as_str()).to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert!(resp.status().is_success(), "Admins should be able to use an api key on admin routes");
+
+        let req = TestRequest::post().uri(&uri).cookie(cookie.clone()).to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert!(resp.status().is_success(), "Failed to retry email");
+        let body: Value = test::read_body_json(resp).await;
+        assert_eq!(json!("pending"), body["status"]);
+        assert_eq!(json!(0), body["attempts"]);
+
+        let req = TestRequest::post().uri(&uri).cookie(cookie).to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(404, resp.status().as_u16(), "Only dead emails can be retried");
+
+        delete(dead.id);
+        User::delete(admin.id).unwrap();
+    }
+
+    // Links either go to a page the frontend is expected to serve, which submits to an api
+    // route, or straight to an api route
+    #[actix_rt::test]
+    async fn test_links_point_to_routes() {
+        let mailer = crate::test::mailer();
+        let conn = db::connection().unwrap();
+
+        let email = format!("{}@cloudmaker.dev", Uuid::new_v4());
+        let invited = format!("{}@cloudmaker.dev", Uuid::new_v4());
+        let user = User::create(&conn, UserMessage { email: email.clone(), password: "test".to_string() }).unwrap();
+        EmailVerificationToken::create(&conn, EmailVerificationTokenMessage { id: None, email: invited.clone() }).unwrap();
+        EmailChangeToken::create(&conn, user.id, format!("{}@cloudmaker.dev", Uuid::new_v4())).unwrap();
+
+        let locale = "en".to_string();
+        let messages = vec![
+            OutboundMessage::Invite { email: invited, locale: locale.clone() },
+            OutboundMessage::ChangeEmail { user_id: user.id, locale: locale.clone() },
+            OutboundMessage::PasswordReset { email: email.clone(), locale: locale.clone() },
+            OutboundMessage::MagicLink { email, locale },
+        ];
+
+        let redis_port = env::var("REDIS_PORT").expect("Redis port not set");
+        let redis_host = env::var("REDIS_HOST").expect("Redis host not set");
+
+        let mut app = test::init_service(
+            App::new()
+                .app_data(mailer.clone())
+                .wrap(RedisSession::new(format!("{}:{}", redis_host, redis_port), &[0; 32]))
+                .configure(auth::init_routes)
+                .configure(account::init_routes)
+        ).await;
+
+        for message in messages {
+            let sent = message.render(&mailer).unwrap().expect("Nothing to send");
+            let text = sent.text().expect("Email has no text part");
+            let link = text.split_whitespace()
+                .find(|word| word.starts_with("http"))
+                .expect("Email has no link");
+            let link = Url::parse(link).expect("Link is not a valid url");
+
+            let req = if link.origin() == mailer.config.frontend_url.origin() {
+                let (_, api_route) = FRONTEND_ROUTES.iter()
+                    .find(|(route, _)| *route == link.path())
+                    .unwrap_or_else(|| panic!("{} is not a documented frontend route", link));
+                TestRequest::post().uri(*api_route).set_json(&json!({}))
+            } else {
+                assert_eq!(mailer.config.app_base_url.origin(), link.origin(), "{} goes neither to the api nor the frontend", link);
+                TestRequest::get().uri(link.path())
+            };
+
+            let resp = test::call_service(&mut app, req.to_request()).await;
+            assert_ne!(404, resp.status().as_u16(), "{} leads to a route that doesn't exist", link);
+        }
+
+        User::delete(user.id).unwrap();
+    }
+}
@@ -0,0 +1,74 @@
+use crate::api_error::ApiError;
+use crate::email::Mailer;
+use crate::outbound_email::OutboundEmail;
+use actix_web::web;
+use std::env;
+use std::time::Duration;
+
+const BATCH_SIZE: i64 = 20;
+
+// Polls the outbox every EMAIL_OUTBOX_INTERVAL seconds. Has to be called from within the actix runtime.
+pub fn start(mailer: web::Data<Mailer>) {
+    let interval = env::var("EMAIL_OUTBOX_INTERVAL")
+        .map(|interval| interval.parse().expect("Email outbox interval must be a number of seconds"))
+        .unwrap_or(5);
+
+    actix_rt::spawn(async move {
+        let mut interval = actix_rt::time::interval(Duration::from_secs(interval));
+        loop {
+            interval.tick().await;
+            if let Err(e) = process_due(&mailer).await {
+                error!("Failed to process outbound emails: {}", e);
+            }
+        }
+    });
+}
+
+// Returns how many emails were sent
+pub async fn process_due(mailer: &web::Data<Mailer>) -> Result<usize, ApiError> {
+    let emails = web::block(|| OutboundEmail::claim_due(BATCH_SIZE)).await?;
+    send(mailer, emails).await
+}
+
+// Only sends the emails to the recipient, see OutboundEmail::claim_due_for
+#[cfg(test)]
+pub async fn process_due_for(mailer: &web::Data<Mailer>, recipient: &str) -> Result<usize, ApiError> {
+    let recipient = recipient.to_string();
+    let emails = web::block(move || OutboundEmail::claim_due_for(&recipient, BATCH_SIZE)).await?;
+    send(mailer, emails).await
+}
+
+async fn send(mailer: &web::Data<Mailer>, emails: Vec<OutboundEmail>) -> Result<usize, ApiError> {
+    let mut sent = 0;
+
+    for outbound_email in emails {
+        let id = outbound_email.id;
+        let attempts = outbound_email.attempts;
+
+        // Rendering looks up the tokens for the links, so it runs on the blocking pool as well
+        let renderer = mailer.clone();
+        let rendered = web::block(move || outbound_email.message()?.render(&renderer)).await;
+
+        let res = match rendered {
+            Ok(Some(email)) => email.send(mailer.clone()).await.map(Some),
+            Ok(None) => Ok(None),
+            Err(e) => Err(ApiError::from(e)),
+        };
+
+        match res {
+            Ok(Some(message_id)) => {
+                web::block(move || OutboundEmail::mark_sent(id, message_id)).await?;
+                sent += 1;
+            },
+            Ok(None) => {
+                web::block(move || OutboundEmail::mark_skipped(id)).await?;
+            },
+            Err(e) => {
+                warn!("Failed to send outbound email {}: {}", id, e);
+                web::block(move || OutboundEmail::mark_failed(id, attempts, &e)).await?;
+            },
+        }
+    }
+
+    Ok(sent)
+}
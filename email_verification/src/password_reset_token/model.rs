@@ -0,0 +1,58 @@
+use crate::api_error::ApiError;
+use crate::db;
+use crate::schema::password_reset_token;
+use crate::user::User;
+use chrono::{NaiveDateTime, Utc, Duration};
+use diesel::prelude::*;
+use rand::Rng;
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+#[derive(Queryable, Insertable)]
+#[table_name = "password_reset_token"]
+pub struct PasswordResetToken {
+    pub id: Vec<u8>,
+    pub user_id: Uuid,
+    pub expires_at: NaiveDateTime,
+    pub created_at: NaiveDateTime,
+}
+
+impl PasswordResetToken {
+    // Only the hash of the token is stored, the returned secret is what gets sent to the user.
+    pub fn create(conn: &PgConnection, user_id: Uuid) -> Result<(Self, Vec<u8>), ApiError> {
+        let secret = rand::thread_rng().gen::<[u8; 32]>().to_vec();
+        let id = Sha256::digest(&secret).to_vec();
+        let created_at = Utc::now().naive_utc();
+        let expires_at = created_at + Duration::hours(1);
+        let token = PasswordResetToken { id, user_id, expires_at, created_at };
+
+        let token = diesel::insert_into(password_reset_token::table)
+            .values(&token)
+            .on_conflict(password_reset_token::user_id)
+            .do_update()
+            .set((
+                password_reset_token::id.eq(&token.id),
+                password_reset_token::created_at.eq(&token.created_at),
+                password_reset_token::expires_at.eq(&token.expires_at),
+            ))
+            .get_result(conn)?;
+
+        Ok((token, secret))
+    }
+
+    pub fn redeem(secret: &[u8], password: String) -> Result<User, ApiError> {
+        let conn = db::connection()?;
+        let id = Sha256::digest(secret).to_vec();
+
+        conn.transaction(|| {
+            let token: PasswordResetToken = diesel::delete(
+                    password_reset_token::table
+                        .filter(password_reset_token::id.eq(&id))
+                        .filter(password_reset_token::expires_at.gt(Utc::now().naive_utc()))
+                )
+                .get_result(&conn)?;
+
+            User::set_password(&conn, token.user_id, password)
+        })
+    }
+}
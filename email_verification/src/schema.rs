@@ -1,9 +1,95 @@
+table! {
+    api_key (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        name -> Text,
+        key_hash -> Bytea,
+        scopes -> Array<Text>,
+        expires_at -> Nullable<Timestamp>,
+        last_used_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    email_change_token (id) {
+        id -> Bytea,
+        user_id -> Uuid,
+        email -> Text,
+        expires_at -> Timestamp,
+        created_at -> Timestamp,
+        salt -> Bytea,
+    }
+}
+
+table! {
+    email_event (id) {
+        id -> Uuid,
+        message_id -> Text,
+        email -> Text,
+        event -> Text,
+        reason -> Nullable<Text>,
+        occurred_at -> Timestamp,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    email_suppression (email) {
+        email -> Text,
+        reason -> Text,
+        message_id -> Nullable<Text>,
+        created_at -> Timestamp,
+    }
+}
+
 table! {
     email_verification_token (id) {
         id -> Bytea,
         email -> Text,
         expires_at -> Timestamp,
         created_at -> Timestamp,
+        salt -> Nullable<Bytea>,
+    }
+}
+
+table! {
+    magic_link_token (id) {
+        id -> Bytea,
+        user_id -> Uuid,
+        expires_at -> Timestamp,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    outbound_email (id) {
+        id -> Uuid,
+        payload -> Text,
+        status -> Text,
+        attempts -> Int4,
+        next_attempt_at -> Timestamp,
+        last_error -> Nullable<Text>,
+        message_id -> Nullable<Text>,
+        sent_at -> Nullable<Timestamp>,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    password_reset_token (id) {
+        id -> Bytea,
+        user_id -> Uuid,
+        expires_at -> Timestamp,
+        created_at -> Timestamp,
+    }
+}
+
+table! {
+    recovery_code (id) {
+        id -> Bytea,
+        user_id -> Uuid,
+        created_at -> Timestamp,
     }
 }
 
@@ -14,10 +100,41 @@ table! {
         password -> Text,
         created_at -> Timestamp,
         updated_at -> Nullable<Timestamp>,
+        session_version -> Int4,
+        totp_secret -> Nullable<Bytea>,
+        totp_enabled -> Bool,
+        is_admin -> Bool,
+        totp_last_step -> Nullable<Int8>,
     }
 }
 
+table! {
+    user_identity (id) {
+        id -> Uuid,
+        user_id -> Uuid,
+        issuer -> Text,
+        subject -> Text,
+        created_at -> Timestamp,
+    }
+}
+
+joinable!(api_key -> user (user_id));
+joinable!(email_change_token -> user (user_id));
+joinable!(magic_link_token -> user (user_id));
+joinable!(password_reset_token -> user (user_id));
+joinable!(recovery_code -> user (user_id));
+joinable!(user_identity -> user (user_id));
+
 allow_tables_to_appear_in_same_query!(
+    api_key,
+    email_change_token,
+    email_event,
+    email_suppression,
     email_verification_token,
+    magic_link_token,
+    outbound_email,
+    password_reset_token,
+    recovery_code,
     user,
+    user_identity,
 );
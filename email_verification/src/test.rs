@@ -0,0 +1,35 @@
+
+use crate::db;
+use crate::email::{EmailConfig, Mailer};
+use actix_web::web;
+use lazy_static::lazy_static;
+use std::env;
+use std::sync::{Arc, Mutex};
+use dotenv::dotenv;
+
+lazy_static! {
+    static ref INITIATED: Arc<Mutex<bool>> = Arc::new(Mutex::new(false));
+}
+
+#[cfg(test)]
+pub fn init() {
+    let mut initiated = INITIATED.lock().unwrap();
+    if *initiated == false {
+        dotenv().ok();
+        env::set_var("EMAIL_TRANSPORT", "memory");
+        for key in &["EMAIL_TOKEN_KEY", "TOTP_ENCRYPTION_KEY"] {
+            if env::var(key).is_err() {
+                env::set_var(key, hex::encode(rand::random::<[u8; 32]>()));
+            }
+        }
+        db::init();
+        *initiated = true;
+    }
+}
+
+#[cfg(test)]
+pub fn mailer() -> web::Data<Mailer> {
+    init();
+    let config = EmailConfig::from_env().expect("Invalid email config");
+    web::Data::new(Mailer::new(config).expect("Failed to initialize email"))
+}
@@ -0,0 +1,36 @@
+use crate::api_error::ApiError;
+use crate::cache;
+use redis::Commands;
+use uuid::Uuid;
+
+// Failed codes are counted for the user as well as for the pending sign in, so signing in
+// with the password again doesn't give an unlimited number of guesses
+const MAX_FAILURES: usize = 10;
+const WINDOW_SECONDS: usize = 15 * 60;
+
+fn key(user_id: Uuid) -> String {
+    format!("two_factor.failures.{}", user_id)
+}
+
+pub fn is_locked(user_id: Uuid) -> Result<bool, ApiError> {
+    let mut cache = cache::connection()?;
+    let failures: Option<usize> = cache.get(key(user_id))?;
+    Ok(failures.unwrap_or(0) >= MAX_FAILURES)
+}
+
+// Returns true when the user has run out of attempts. The window starts with the first failure.
+pub fn record_failure(user_id: Uuid) -> Result<bool, ApiError> {
+    let mut cache = cache::connection()?;
+    let failures: usize = cache.incr(key(user_id), 1)?;
+    if failures == 1 {
+        let _: () = cache.expire(key(user_id), WINDOW_SECONDS)?;
+    }
+
+    Ok(failures >= MAX_FAILURES)
+}
+
+pub fn clear(user_id: Uuid) -> Result<(), ApiError> {
+    let mut cache = cache::connection()?;
+    let _: () = cache.del(key(user_id))?;
+    Ok(())
+}
@@ -0,0 +1,9 @@
+mod attempts;
+mod recovery_code;
+mod routes;
+mod tests;
+mod totp;
+
+pub use recovery_code::RecoveryCode;
+pub use routes::init_routes;
+pub use totp::{init, Totp};
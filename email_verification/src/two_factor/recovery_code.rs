@@ -0,0 +1,65 @@
+use crate::api_error::ApiError;
+use crate::db;
+use crate::schema::recovery_code;
+use chrono::{NaiveDateTime, Utc};
+use diesel::prelude::*;
+use rand::Rng;
+use sha2::{Digest, Sha256};
+use uuid::Uuid;
+
+const NUM_CODES: usize = 10;
+
+#[derive(Queryable, Insertable)]
+#[table_name = "recovery_code"]
+pub struct RecoveryCode {
+    pub id: Vec<u8>,
+    pub user_id: Uuid,
+    pub created_at: NaiveDateTime,
+}
+
+impl RecoveryCode {
+    // Replaces any existing codes for the user. Only the hashes are stored,
+    // so the returned codes can't be shown again later.
+    pub fn generate(user_id: Uuid) -> Result<Vec<String>, ApiError> {
+        let conn = db::connection()?;
+
+        let codes: Vec<String> = (0..NUM_CODES)
+            .map(|_| hex::encode(rand::thread_rng().gen::<[u8; 5]>()))
+            .collect();
+
+        let created_at = Utc::now().naive_utc();
+        let recovery_codes: Vec<RecoveryCode> = codes.iter()
+            .map(|code| RecoveryCode { id: hash(code), user_id, created_at })
+            .collect();
+
+        conn.transaction::<_, ApiError, _>(|| {
+            diesel::delete(recovery_code::table.filter(recovery_code::user_id.eq(user_id)))
+                .execute(&conn)?;
+
+            diesel::insert_into(recovery_code::table)
+                .values(&recovery_codes)
+                .execute(&conn)?;
+
+            Ok(())
+        })?;
+
+        Ok(codes)
+    }
+
+    pub fn redeem(user_id: Uuid, code: &str) -> Result<bool, ApiError> {
+        let conn = db::connection()?;
+
+        let res = diesel::delete(
+                recovery_code::table
+                    .filter(recovery_code::id.eq(hash(code)))
+                    .filter(recovery_code::user_id.eq(user_id))
+            )
+            .execute(&conn)?;
+
+        Ok(res == 1)
+    }
+}
+
+fn hash(code: &str) -> Vec<u8> {
+    Sha256::digest(code.trim().to_lowercase().as_bytes()).to_vec()
+}
@@ -0,0 +1,102 @@
+use crate::api_error::ApiError;
+use crate::auth;
+use crate::two_factor::{attempts, RecoveryCode, Totp};
+use crate::user::User;
+use actix_web::{post, web, HttpRequest, HttpResponse};
+use actix_session::Session;
+use serde::Deserialize;
+use serde_json::json;
+
+// Failed codes before the pending sign in is dropped
+const MAX_SESSION_FAILURES: u32 = 5;
+
+#[derive(Deserialize)]
+struct CodeMessage {
+    code: String,
+}
+
+#[post("/me/2fa/setup")]
+async fn setup(session: Session) -> Result<HttpResponse, ApiError> {
+    let user = auth::current_user(&session)?;
+
+    if user.totp_enabled {
+        return Err(ApiError::new(409, "Two-factor authentication is already enabled"));
+    }
+
+    let totp = Totp::generate();
+    User::set_totp(user.id, Some(totp.encrypt()?), false)?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "secret": totp.secret(),
+        "uri": totp.uri(&user.email)?,
+    })))
+}
+
+#[post("/me/2fa/confirm")]
+async fn confirm(body: web::Json<CodeMessage>, session: Session) -> Result<HttpResponse, ApiError> {
+    let user = auth::current_user(&session)?;
+
+    let totp = match (&user.totp_secret, user.totp_enabled) {
+        (Some(secret), false) => Totp::decrypt(secret)?,
+        (_, true) => return Err(ApiError::new(409, "Two-factor authentication is already enabled")),
+        (None, _) => return Err(ApiError::new(400, "Two-factor authentication has not been set up")),
+    };
+
+    let step = totp.verify(&body.code, user.totp_last_step)
+        .ok_or(ApiError::new(403, "Invalid code"))?;
+
+    if !User::use_totp_step(user.id, step)? {
+        return Err(ApiError::new(403, "Invalid code"));
+    }
+
+    User::set_totp(user.id, user.totp_secret, true)?;
+    let recovery_codes = RecoveryCode::generate(user.id)?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "message": "Two-factor authentication enabled",
+        "recovery_codes": recovery_codes,
+    })))
+}
+
+#[post("/sign-in/2fa")]
+async fn sign_in(body: web::Json<CodeMessage>, session: Session, req: HttpRequest) -> Result<HttpResponse, ApiError> {
+    let user = auth::two_factor_user(&session)?;
+
+    if attempts::is_locked(user.id)? {
+        auth::cancel_two_factor(&session);
+        return Err(ApiError::new(429, "Too many attempts, try again later"));
+    }
+
+    if !verify(&user, &body.code)? {
+        let session_failures = auth::record_two_factor_failure(&session)?;
+        let is_locked = attempts::record_failure(user.id)?;
+
+        if is_locked || session_failures >= MAX_SESSION_FAILURES {
+            auth::cancel_two_factor(&session);
+        }
+
+        return Err(ApiError::new(401, "Invalid code"));
+    }
+
+    attempts::clear(user.id)?;
+    auth::sign_in(&session, &user, &req)?;
+
+    Ok(HttpResponse::Ok().json(user))
+}
+
+// Accepts either a code from the authenticator app or one of the unused recovery codes
+fn verify(user: &User, code: &str) -> Result<bool, ApiError> {
+    if let Some(secret) = &user.totp_secret {
+        if let Some(step) = Totp::decrypt(secret)?.verify(code, user.totp_last_step) {
+            return User::use_totp_step(user.id, step);
+        }
+    }
+
+    RecoveryCode::redeem(user.id, code)
+}
+
+pub fn init_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(setup);
+    cfg.service(confirm);
+    cfg.service(sign_in);
+}
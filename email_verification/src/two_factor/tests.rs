@@ -0,0 +1,124 @@
+
+#[cfg(test)]
+mod tests {
+    use crate::auth;
+    use crate::cache;
+    use crate::db;
+    use crate::two_factor::{init_routes, RecoveryCode, Totp};
+    use crate::user::{User, UserMessage};
+    use actix_redis::RedisSession;
+    use actix_web::{test::{self, TestRequest}, App};
+    use chrono::Utc;
+    use redis::Commands;
+    use serde_json::json;
+    use std::env;
+    use uuid::Uuid;
+
+    // The SHA1 test vectors from RFC 6238, appendix B. The codes there have 8 digits, the last 6 are ours.
+    #[test]
+    fn test_rfc6238_vectors() {
+        let totp = Totp::new(b"12345678901234567890".to_vec());
+
+        let vectors = [
+            (59, 287082),
+            (1111111109, 81804),
+            (1111111111, 50471),
+            (1234567890, 5924),
+            (2000000000, 279037),
+            (20000000000, 353130),
+        ];
+
+        for (time, code) in vectors.iter() {
+            assert_eq!(*code, totp.code_at(time / 30), "Wrong code at {}", time);
+        }
+    }
+
+    #[test]
+    fn test_codes_are_not_accepted_twice() {
+        let totp = Totp::generate();
+        let step = Utc::now().timestamp() / 30;
+        let code = format!("{:06}", totp.code_at(step as u64));
+
+        assert_eq!(Some(step), totp.verify(&code, None));
+        assert_eq!(Some(step), totp.verify(&code, Some(step - 1)));
+        assert_eq!(None, totp.verify(&code, Some(step)), "The code for the last used step should be rejected");
+        assert_eq!(None, totp.verify(&code, Some(step + 1)), "Codes older than the last used step should be rejected");
+        assert_eq!(None, totp.verify("12345", None));
+        assert_eq!(None, totp.verify("abcdef", None));
+    }
+
+    #[actix_rt::test]
+    async fn test_sign_in_limits_attempts_and_replays() {
+        crate::test::init();
+
+        let email = format!("{}@cloudmaker.dev", Uuid::new_v4());
+        let user = User::create(&db::connection().unwrap(), UserMessage { email: email.clone(), password: "test".to_string() })
+            .expect("Failed to create user");
+        let totp = Totp::generate();
+        User::set_totp(user.id, Some(totp.encrypt().unwrap()), true).unwrap();
+        let recovery_codes = RecoveryCode::generate(user.id).unwrap();
+        let code = format!("{:06}", totp.code_at((Utc::now().timestamp() / 30) as u64));
+
+        let redis_port = env::var("REDIS_PORT").expect("Redis port not set");
+        let redis_host = env::var("REDIS_HOST").expect("Redis host not set");
+
+        let mut app = test::init_service(
+            App::new()
+                .wrap(RedisSession::new(format!("{}:{}", redis_host, redis_port), &[0; 32]))
+                .configure(auth::init_routes)
+                .configure(init_routes)
+        ).await;
+
+        let credentials = json!({ "email": email, "password": "test" });
+
+        let req = TestRequest::post().uri("/sign-in").set_json(&credentials).to_request();
+        let resp = test::call_service(&mut app, req).await;
+        let cookie = resp.response().cookies().next().expect("No session cookie").into_owned();
+
+        let req = TestRequest::post().uri("/sign-in/2fa").cookie(cookie).set_json(&json!({ "code": code })).to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert!(resp.status().is_success(), "Failed to sign in with the code");
+
+        // A new sign in, where the code that was just used counts as the first failure
+        let req = TestRequest::post().uri("/sign-in").set_json(&credentials).to_request();
+        let resp = test::call_service(&mut app, req).await;
+        let cookie = resp.response().cookies().next().expect("No session cookie").into_owned();
+
+        let req = TestRequest::post().uri("/sign-in/2fa").cookie(cookie.clone()).set_json(&json!({ "code": code })).to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(401, resp.status().as_u16(), "A used code should not work again");
+
+        for _ in 0..4 {
+            let req = TestRequest::post().uri("/sign-in/2fa").cookie(cookie.clone()).set_json(&json!({ "code": "000000" })).to_request();
+            let resp = test::call_service(&mut app, req).await;
+            assert_eq!(401, resp.status().as_u16());
+        }
+
+        let req = TestRequest::post().uri("/sign-in/2fa").cookie(cookie).set_json(&json!({ "code": recovery_codes[0] })).to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(401, resp.status().as_u16(), "The sign in should be dropped after 5 failures");
+
+        // Signing in with the password again gives 5 more tries, which is all the user has left
+        let req = TestRequest::post().uri("/sign-in").set_json(&credentials).to_request();
+        let resp = test::call_service(&mut app, req).await;
+        let cookie = resp.response().cookies().next().expect("No session cookie").into_owned();
+
+        for _ in 0..5 {
+            let req = TestRequest::post().uri("/sign-in/2fa").cookie(cookie.clone()).set_json(&json!({ "code": "000000" })).to_request();
+            let resp = test::call_service(&mut app, req).await;
+            assert_eq!(401, resp.status().as_u16());
+        }
+
+        let req = TestRequest::post().uri("/sign-in").set_json(&credentials).to_request();
+        let resp = test::call_service(&mut app, req).await;
+        let cookie = resp.response().cookies().next().expect("No session cookie").into_owned();
+
+        let req = TestRequest::post().uri("/sign-in/2fa").cookie(cookie).set_json(&json!({ "code": recovery_codes[0] })).to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(429, resp.status().as_u16(), "The user should be locked out after 10 failures");
+
+        let mut cache = cache::connection().unwrap();
+        let _: () = cache.del(format!("two_factor.failures.{}", user.id)).unwrap();
+        User::delete(user.id).unwrap();
+    }
+}
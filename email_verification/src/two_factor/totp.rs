@@ -0,0 +1,131 @@
+use crate::api_error::ApiError;
+use aes_gcm::Aes256Gcm;
+use aes_gcm::aead::{Aead, NewAead, generic_array::GenericArray};
+use base32::Alphabet;
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use lazy_static::lazy_static;
+use rand::Rng;
+use reqwest::Url;
+use sha1::Sha1;
+use std::env;
+
+const ISSUER: &str = "Cloudmaker";
+const PERIOD: i64 = 30;
+const DIGITS: u32 = 6;
+const NONCE_LENGTH: usize = 12;
+
+lazy_static! {
+    static ref ENCRYPTION_KEY: Vec<u8> = {
+        let key = env::var("TOTP_ENCRYPTION_KEY").expect("Totp encryption key not set");
+        let key = hex::decode(key).expect("Totp encryption key must be hex encoded");
+        assert_eq!(32, key.len(), "Totp encryption key must be 32 bytes");
+        key
+    };
+}
+
+pub fn init() {
+    info!("Initializing two-factor authentication");
+    lazy_static::initialize(&ENCRYPTION_KEY);
+}
+
+// Time-based one-time passwords as described in RFC 6238, using the defaults
+// authenticator apps expect: HMAC-SHA1, 30 second steps and 6 digits.
+pub struct Totp {
+    secret: Vec<u8>,
+}
+
+impl Totp {
+    pub fn generate() -> Self {
+        Totp::new(rand::thread_rng().gen::<[u8; 20]>().to_vec())
+    }
+
+    pub(super) fn new(secret: Vec<u8>) -> Self {
+        Totp { secret }
+    }
+
+    pub fn decrypt(encrypted: &[u8]) -> Result<Self, ApiError> {
+        if encrypted.len() <= NONCE_LENGTH {
+            return Err(ApiError::new(500, "Failed to decrypt totp secret: Too short"));
+        }
+
+        let (nonce, ciphertext) = encrypted.split_at(NONCE_LENGTH);
+        let secret = cipher().decrypt(GenericArray::from_slice(nonce), ciphertext)
+            .map_err(|_| ApiError::new(500, "Failed to decrypt totp secret"))?;
+
+        Ok(Totp { secret })
+    }
+
+    // The random nonce is stored in front of the ciphertext
+    pub fn encrypt(&self) -> Result<Vec<u8>, ApiError> {
+        let nonce = rand::thread_rng().gen::<[u8; NONCE_LENGTH]>();
+        let ciphertext = cipher().encrypt(GenericArray::from_slice(&nonce), self.secret.as_ref())
+            .map_err(|_| ApiError::new(500, "Failed to encrypt totp secret"))?;
+
+        let mut encrypted = nonce.to_vec();
+        encrypted.extend(ciphertext);
+
+        Ok(encrypted)
+    }
+
+    pub fn secret(&self) -> String {
+        base32::encode(Alphabet::RFC4648 { padding: false }, &self.secret)
+    }
+
+    pub fn uri(&self, account: &str) -> Result<String, ApiError> {
+        let mut url = Url::parse("otpauth://totp/")
+            .map_err(|e| ApiError::new(500, format!("Failed to build otpauth uri: {}", e)))?;
+
+        url.path_segments_mut()
+            .map_err(|_| ApiError::new(500, "Failed to build otpauth uri"))?
+            .push(&format!("{}:{}", ISSUER, account));
+
+        url.query_pairs_mut()
+            .append_pair("secret", &self.secret())
+            .append_pair("issuer", ISSUER)
+            .append_pair("algorithm", "SHA1")
+            .append_pair("digits", &DIGITS.to_string())
+            .append_pair("period", &PERIOD.to_string());
+
+        Ok(url.into_string())
+    }
+
+    // Accepts the codes for the previous and next step as well, to allow for clock drift. Codes
+    // for the last used step or earlier are rejected, so a code can't be used again. Returns the
+    // step of the code, which should be stored as the last used one.
+    pub fn verify(&self, code: &str, last_step: Option<i64>) -> Option<i64> {
+        let code = code.trim();
+        if code.len() != DIGITS as usize {
+            return None;
+        }
+
+        let code: u32 = match code.parse() {
+            Ok(code) => code,
+            Err(_) => return None,
+        };
+
+        let step = Utc::now().timestamp() / PERIOD;
+        (step - 1..=step + 1)
+            .filter(|step| last_step.map_or(true, |last_step| *step > last_step))
+            .find(|step| self.code_at(*step as u64) == code)
+    }
+
+    pub(super) fn code_at(&self, step: u64) -> u32 {
+        let mut mac = Hmac::<Sha1>::new_varkey(&self.secret)
+            .expect("Hmac can take a key of any size");
+        mac.input(&step.to_be_bytes());
+        let hash = mac.result().code();
+
+        let offset = (hash[hash.len() - 1] & 0xf) as usize;
+        let binary = (u32::from(hash[offset]) & 0x7f) << 24
+            | u32::from(hash[offset + 1]) << 16
+            | u32::from(hash[offset + 2]) << 8
+            | u32::from(hash[offset + 3]);
+
+        binary % 10u32.pow(DIGITS)
+    }
+}
+
+fn cipher() -> Aes256Gcm {
+    Aes256Gcm::new(GenericArray::from_slice(&ENCRYPTION_KEY))
+}
@@ -1,5 +1,8 @@
 mod model;
+mod password;
 mod routes;
+mod tests;
 
 pub use model::*;
+pub use password::init;
 pub use routes::init_routes;
@@ -1,8 +1,9 @@
 use crate::api_error::ApiError;
 use crate::db;
 use crate::schema::user;
-use argon2::Config;
+use super::password::{DUMMY_PASSWORD, PASSWORD_CONFIG};
 use chrono::{NaiveDateTime, Utc};
+use diesel::pg::PgConnection;
 use diesel::prelude::*;
 use rand::Rng;
 use serde::{Deserialize, Serialize};
@@ -24,6 +25,15 @@ pub struct User {
     pub password: String,
     pub created_at: NaiveDateTime,
     pub updated_at: Option<NaiveDateTime>,
+    #[serde(skip_serializing)]
+    pub session_version: i32,
+    #[serde(skip_serializing)]
+    pub totp_secret: Option<Vec<u8>>,
+    pub totp_enabled: bool,
+    pub is_admin: bool,
+    // The time step of the last accepted totp code, so a code can't be used twice
+    #[serde(skip_serializing)]
+    pub totp_last_step: Option<i64>,
 }
 
 impl User {
@@ -56,14 +66,12 @@ impl User {
         Ok(user)
     }
 
-    pub fn create(user: UserMessage) -> Result<Self, ApiError> {
-        let conn = db::connection()?;
-
+    pub fn create(conn: &PgConnection, user: UserMessage) -> Result<Self, ApiError> {
         let mut user = User::from(user);
         user.hash_password()?;
         let user = diesel::insert_into(user::table)
             .values(user)
-            .get_result(&conn)?;
+            .get_result(conn)?;
 
         Ok(user)
     }
@@ -71,14 +79,83 @@ impl User {
     pub fn update(id: Uuid, user: UserMessage) -> Result<Self, ApiError> {
         let conn = db::connection()?;
 
+        let mut user = User::from(user);
+        user.hash_password()?;
+
         let user = diesel::update(user::table)
             .filter(user::id.eq(id))
-            .set(user)
+            .set((
+                user::email.eq(user.email),
+                user::password.eq(user.password),
+                user::updated_at.eq(Utc::now().naive_utc()),
+                user::session_version.eq(user::session_version + 1),
+            ))
             .get_result(&conn)?;
 
         Ok(user)
     }
 
+    pub fn set_password(conn: &PgConnection, id: Uuid, password: String) -> Result<Self, ApiError> {
+        let mut user: User = user::table
+            .filter(user::id.eq(id))
+            .first(conn)?;
+
+        user.password = password;
+        user.hash_password()?;
+
+        let user = diesel::update(user::table)
+            .filter(user::id.eq(id))
+            .set((
+                user::password.eq(user.password),
+                user::updated_at.eq(Utc::now().naive_utc()),
+                user::session_version.eq(user::session_version + 1),
+            ))
+            .get_result(conn)?;
+
+        Ok(user)
+    }
+
+    pub fn set_email(conn: &PgConnection, id: Uuid, email: String) -> Result<Self, ApiError> {
+        let user = diesel::update(user::table)
+            .filter(user::id.eq(id))
+            .set((
+                user::email.eq(email),
+                user::updated_at.eq(Utc::now().naive_utc()),
+            ))
+            .get_result(conn)?;
+
+        Ok(user)
+    }
+
+    pub fn set_totp(id: Uuid, totp_secret: Option<Vec<u8>>, totp_enabled: bool) -> Result<Self, ApiError> {
+        let conn = db::connection()?;
+
+        let user = diesel::update(user::table)
+            .filter(user::id.eq(id))
+            .set((
+                user::totp_secret.eq(totp_secret),
+                user::totp_enabled.eq(totp_enabled),
+                user::updated_at.eq(Utc::now().naive_utc()),
+            ))
+            .get_result(&conn)?;
+
+        Ok(user)
+    }
+
+    // Records the step of an accepted totp code. Returns false when the step, or a later one,
+    // has already been used, which also settles two requests racing with the same code.
+    pub fn use_totp_step(id: Uuid, step: i64) -> Result<bool, ApiError> {
+        let conn = db::connection()?;
+
+        let res = diesel::update(user::table)
+            .filter(user::id.eq(id))
+            .filter(user::totp_last_step.is_null().or(user::totp_last_step.lt(step)))
+            .set(user::totp_last_step.eq(step))
+            .execute(&conn)?;
+
+        Ok(res == 1)
+    }
+
     pub fn delete(id: Uuid) -> Result<usize, ApiError> {
         let conn = db::connection()?;
 
@@ -93,7 +170,7 @@ impl User {
 
     pub fn hash_password(&mut self) -> Result<(), ApiError> {
         let salt: [u8; 32] = rand::thread_rng().gen();
-        let config = Config::default();
+        let config = PASSWORD_CONFIG.argon2();
 
         self.password = argon2::hash_encoded(self.password.as_bytes(), &salt, &config)
             .map_err(|e| ApiError::new(500, format!("Failed to hash password: {}", e)))?;
@@ -105,6 +182,33 @@ impl User {
         argon2::verify_encoded(&self.password, password)
             .map_err(|e| ApiError::new(500, format!("Failed to verify password: {}", e)))
     }
+
+    // Hashes created with older argon2 parameters are replaced once we know the password.
+    pub fn rehash_password_if_outdated(&mut self, password: &str) -> Result<(), ApiError> {
+        if PASSWORD_CONFIG.is_current(&self.password) {
+            return Ok(());
+        }
+
+        self.password = password.to_string();
+        self.hash_password()?;
+
+        let conn = db::connection()?;
+        diesel::update(user::table)
+            .filter(user::id.eq(self.id))
+            .set(user::password.eq(&self.password))
+            .execute(&conn)?;
+
+        Ok(())
+    }
+
+    // Runs the same work as verify_password against a throwaway hash, so a
+    // missing user takes as long to reject as a wrong password.
+    pub fn verify_dummy_password(password: &[u8]) -> Result<bool, ApiError> {
+        argon2::verify_encoded(&DUMMY_PASSWORD, password)
+            .map_err(|e| ApiError::new(500, format!("Failed to verify password: {}", e)))?;
+
+        Ok(false)
+    }
 }
 
 impl From<UserMessage> for User {
@@ -115,6 +219,11 @@ impl From<UserMessage> for User {
             password: user.password,
             created_at: Utc::now().naive_utc(),
             updated_at: None,
+            session_version: 0,
+            totp_secret: None,
+            totp_enabled: false,
+            is_admin: false,
+            totp_last_step: None,
         }
     }
 }
@@ -1,41 +1,61 @@
-
 use crate::api_error::ApiError;
+use crate::auth::Authenticated;
+use crate::db;
 use crate::user::{User, UserMessage};
 use actix_web::{delete, get, post, put, web, HttpResponse};
 use serde_json::json;
 use uuid::Uuid;
 
 #[get("/users")]
-async fn find_all() -> Result<HttpResponse, ApiError> {
+async fn find_all(auth: Authenticated) -> Result<HttpResponse, ApiError> {
+    auth.require_scope("users:read")?;
+    auth.require_admin()?;
+
     let users = User::find_all()?;
     Ok(HttpResponse::Ok().json(users))
 }
 
 #[get("/users/{id}")]
-async fn find(id: web::Path<Uuid>) -> Result<HttpResponse, ApiError> {
-    let user = User::find(id.into_inner())?;
+async fn find(id: web::Path<Uuid>, auth: Authenticated) -> Result<HttpResponse, ApiError> {
+    auth.require_scope("users:read")?;
+    let id = id.into_inner();
+
+    if auth.user.id != id {
+        auth.require_admin()?;
+    }
+
+    let user = User::find(id)?;
     Ok(HttpResponse::Ok().json(user))
 }
 
 #[post("/users")]
-async fn create(user: web::Json<UserMessage>) -> Result<HttpResponse, ApiError> {
-    let user = User::create(user.into_inner())?;
+async fn create(user: web::Json<UserMessage>, auth: Authenticated) -> Result<HttpResponse, ApiError> {
+    auth.require_scope("users:write")?;
+    auth.require_admin()?;
+
+    let user = User::create(&db::connection()?, user.into_inner())?;
     Ok(HttpResponse::Ok().json(user))
 }
 
+// Users change their own email and password through /me/email and /me/password, which verify them
 #[put("/users/{id}")]
-async fn update(id: web::Path<Uuid>, user: web::Json<UserMessage>) -> Result<HttpResponse, ApiError> {
+async fn update(id: web::Path<Uuid>, user: web::Json<UserMessage>, auth: Authenticated) -> Result<HttpResponse, ApiError> {
+    auth.require_scope("users:write")?;
+    auth.require_admin()?;
+
     let user = User::update(id.into_inner(), user.into_inner())?;
     Ok(HttpResponse::Ok().json(user))
 }
 
 #[delete("/users/{id}")]
-async fn delete(id: web::Path<Uuid>) -> Result<HttpResponse, ApiError> {
+async fn delete(id: web::Path<Uuid>, auth: Authenticated) -> Result<HttpResponse, ApiError> {
+    auth.require_scope("users:write")?;
+    auth.require_admin()?;
+
     let num_deleted = User::delete(id.into_inner())?;
     Ok(HttpResponse::Ok().json(json!({ "deleted": num_deleted })))
 }
 
-#[allow(dead_code)]
 pub fn init_routes(cfg: &mut web::ServiceConfig) {
     cfg.service(find_all);
     cfg.service(find);
@@ -0,0 +1,113 @@
+
+#[cfg(test)]
+mod tests {
+    use crate::api_key::{ApiKey, ApiKeyMessage};
+    use crate::db;
+    use crate::schema::user;
+    use crate::user::password::PASSWORD_CONFIG;
+    use crate::user::{init_routes, User, UserMessage};
+    use actix_web::{test::{self, TestRequest}, App};
+    use argon2::{Config, Variant, Version};
+    use diesel::prelude::*;
+    use serde_json::json;
+    use uuid::Uuid;
+
+    fn hash(config: &Config) -> String {
+        argon2::hash_encoded(b"password", &[0; 16], config).unwrap()
+    }
+
+    #[test]
+    fn test_is_current() {
+        crate::test::init();
+        let current = PASSWORD_CONFIG.argon2();
+
+        assert!(PASSWORD_CONFIG.is_current(&hash(&current)), "A hash with the current parameters should be current");
+
+        let outdated = vec![
+            Config { mem_cost: current.mem_cost * 2, ..PASSWORD_CONFIG.argon2() },
+            Config { time_cost: current.time_cost + 1, ..PASSWORD_CONFIG.argon2() },
+            Config { lanes: current.lanes + 1, ..PASSWORD_CONFIG.argon2() },
+            Config { version: Version::Version10, ..PASSWORD_CONFIG.argon2() },
+            Config {
+                variant: match current.variant {
+                    Variant::Argon2id => Variant::Argon2i,
+                    _ => Variant::Argon2id,
+                },
+                ..PASSWORD_CONFIG.argon2()
+            },
+        ];
+
+        for config in &outdated {
+            let encoded = hash(config);
+            assert!(!PASSWORD_CONFIG.is_current(&encoded), "{} should be outdated", encoded);
+        }
+
+        assert!(!PASSWORD_CONFIG.is_current("not a hash"));
+        assert!(!PASSWORD_CONFIG.is_current(""));
+    }
+
+    #[actix_rt::test]
+    async fn test_user_routes_require_scopes() {
+        crate::test::init();
+
+        let conn = db::connection().unwrap();
+        let mut users = Vec::new();
+        for _ in 0..2 {
+            let email = format!("{}@cloudmaker.dev", Uuid::new_v4());
+            users.push(User::create(&conn, UserMessage { email, password: "test".to_string() }).unwrap());
+        }
+        let (admin, user) = (&users[0], &users[1]);
+
+        diesel::update(user::table.filter(user::id.eq(admin.id)))
+            .set(user::is_admin.eq(true))
+            .execute(&conn)
+            .unwrap();
+
+        let api_key = |user_id, scopes: &[&str]| {
+            let body = ApiKeyMessage {
+                name: "test".to_string(),
+                scopes: scopes.iter().map(|scope| scope.to_string()).collect(),
+                expires_at: None,
+            };
+            let (_, secret) = ApiKey::create(user_id, body).unwrap();
+            format!("ApiKey {}", secret)
+        };
+
+        let user_reader = api_key(user.id, &["users:read"]);
+        let admin_reader = api_key(admin.id, &["users:read"]);
+        let admin_writer = api_key(admin.id, &["users:read", "users:write"]);
+
+        let mut app = test::init_service(App::new().configure(init_routes)).await;
+
+        let user_uri = format!("/users/{}", user.id);
+        let admin_uri = format!("/users/{}", admin.id);
+        let update = json!({ "email": format!("{}@cloudmaker.dev", Uuid::new_v4()), "password": "new" });
+
+        let requests = vec![
+            (TestRequest::get().uri("/users"), None, 401),
+            (TestRequest::get().uri(&user_uri), Some(&user_reader), 200),
+            (TestRequest::get().uri(&admin_uri), Some(&user_reader), 403),
+            (TestRequest::get().uri("/users"), Some(&user_reader), 403),
+            (TestRequest::get().uri("/users"), Some(&admin_reader), 200),
+            (TestRequest::put().uri(&user_uri).set_json(&update), Some(&user_reader), 403),
+            (TestRequest::put().uri(&user_uri).set_json(&update), Some(&admin_reader), 403),
+            (TestRequest::delete().uri(&user_uri), Some(&admin_reader), 403),
+            (TestRequest::put().uri(&user_uri).set_json(&update), Some(&admin_writer), 200),
+            (TestRequest::delete().uri(&user_uri), Some(&admin_writer), 200),
+        ];
+
+        for (req, authorization, status) in requests {
+            let req = match authorization {
+                Some(authorization) => req.header("Authorization", authorization.as_str()),
+                None => req,
+            };
+            let resp = test::call_service(&mut app, req.to_request()).await;
+            assert_eq!(status, resp.status().as_u16());
+        }
+
+        let updated = User::find_by_email(update["email"].as_str().unwrap().to_string());
+        assert_eq!(Some(404), updated.err().map(|e| e.status_code), "The updated user should be deleted");
+
+        User::delete(admin.id).unwrap();
+    }
+}
@@ -0,0 +1,47 @@
+use crate::api_error::ApiError;
+use crate::db;
+use crate::schema::user_identity;
+use chrono::{NaiveDateTime, Utc};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+// Links the subject of an external identity provider to one of our users
+#[derive(Serialize, Deserialize, Queryable, Insertable)]
+#[table_name = "user_identity"]
+pub struct UserIdentity {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub issuer: String,
+    pub subject: String,
+    pub created_at: NaiveDateTime,
+}
+
+impl UserIdentity {
+    pub fn find(issuer: &str, subject: &str) -> Result<Self, ApiError> {
+        let conn = db::connection()?;
+
+        let identity = user_identity::table
+            .filter(user_identity::issuer.eq(issuer))
+            .filter(user_identity::subject.eq(subject))
+            .first(&conn)?;
+
+        Ok(identity)
+    }
+
+    pub fn create(conn: &PgConnection, user_id: Uuid, issuer: String, subject: String) -> Result<Self, ApiError> {
+        let identity = UserIdentity {
+            id: Uuid::new_v4(),
+            user_id,
+            issuer,
+            subject,
+            created_at: Utc::now().naive_utc(),
+        };
+
+        let identity = diesel::insert_into(user_identity::table)
+            .values(identity)
+            .get_result(conn)?;
+
+        Ok(identity)
+    }
+}